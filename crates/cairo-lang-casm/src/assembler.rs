@@ -1,10 +1,11 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+use core::fmt::Display;
 
 use num_bigint::{BigInt, ToBigInt};
 
 use crate::hints::Hint;
-use crate::instructions::{Instruction, InstructionBody};
+use crate::instructions::{Instruction, InstructionBody, QM31AssertEqInstruction};
 use crate::operand::{DerefOrImmediate, Operation, Register, ResOperand};
 
 #[cfg(test)]
@@ -100,11 +101,39 @@ pub struct AssembledCairoProgram {
     pub hints: Vec<(usize, Vec<Hint>)>,
 }
 
+/// An error encountered while assembling an instruction.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AssembleError {
+    /// The instruction has no opcode extension in the Cairo VM yet, as is the case of keccak.
+    NoOpcodeExtension,
+    /// The `ap++` of the instruction does not match its body: it is set on an add_ap, call or ret,
+    /// or missing from a blake2s, whose encodings fix the ap update.
+    InvalidIncAp,
+}
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AssembleError::NoOpcodeExtension => write!(f, "Instruction has no opcode extension"),
+            AssembleError::InvalidIncAp => write!(f, "Instruction has an unsupported ap++ flag"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AssembleError {}
+
 impl Instruction {
-    pub fn assemble(&self) -> InstructionRepr {
-        match &self.body {
+    /// Returns the low level representation of the instruction.
+    ///
+    /// Fails with [AssembleError::NoOpcodeExtension] for the keccak instruction, which the Cairo VM
+    /// cannot run yet, and with [AssembleError::InvalidIncAp] if the `ap++` of the instruction has
+    /// no encoding.
+    pub fn assemble(&self) -> Result<InstructionRepr, AssembleError> {
+        Ok(match &self.body {
             InstructionBody::AddAp(insn) => {
-                assert!(!self.inc_ap, "An add_ap instruction cannot have an ap++.");
+                if self.inc_ap {
+                    return Err(AssembleError::InvalidIncAp);
+                }
                 let res = insn.operand.to_res_description();
                 InstructionRepr {
                     off0: -1,
@@ -140,7 +169,7 @@ impl Instruction {
                     opcode_extension: OpcodeExtension::Stone,
                 }
             }
-            InstructionBody::QM31AssertEq(insn) => {
+            InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => {
                 let res = insn.b.to_res_description();
                 InstructionRepr {
                     off0: insn.a.offset,
@@ -159,7 +188,9 @@ impl Instruction {
                 }
             }
             InstructionBody::Call(insn) => {
-                assert!(!self.inc_ap, "A call instruction cannot have an ap++.");
+                if self.inc_ap {
+                    return Err(AssembleError::InvalidIncAp);
+                }
                 let res = insn.target.to_res_description();
                 InstructionRepr {
                     off0: 0,
@@ -214,7 +245,9 @@ impl Instruction {
                 }
             }
             InstructionBody::Ret(_) => {
-                assert!(!self.inc_ap);
+                if self.inc_ap {
+                    return Err(AssembleError::InvalidIncAp);
+                }
                 InstructionRepr {
                     off0: -2,
                     off1: -1,
@@ -232,7 +265,9 @@ impl Instruction {
                 }
             }
             InstructionBody::Blake2sCompress(insn) => {
-                assert!(self.inc_ap);
+                if !self.inc_ap {
+                    return Err(AssembleError::InvalidIncAp);
+                }
                 InstructionRepr {
                     off0: insn.byte_count.offset,
                     off1: insn.state.offset,
@@ -253,7 +288,8 @@ impl Instruction {
                     },
                 }
             }
-        }
+            InstructionBody::Keccak(_) => return Err(AssembleError::NoOpcodeExtension),
+        })
     }
 }
 
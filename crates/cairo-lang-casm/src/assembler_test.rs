@@ -1,17 +1,24 @@
 use num_bigint::ToBigInt;
 use pretty_assertions::assert_eq;
+use test_case::test_case;
 use test_log::test;
 
 use super::InstructionRepr;
-use crate::assembler::{ApUpdate, FpUpdate, Op1Addr, Opcode, OpcodeExtension, PcUpdate, Res};
+use crate::assembler::{
+    ApUpdate, AssembleError, FpUpdate, Op1Addr, Opcode, OpcodeExtension, PcUpdate, Res,
+};
 use crate::casm;
 use crate::inline::CasmContext;
-use crate::operand::Register;
+use crate::instructions::{
+    AddApInstruction, Blake2sCompressInstruction, CallInstruction, Instruction, InstructionBody,
+    RetInstruction,
+};
+use crate::operand::{CellRef, Register, ResOperand};
 
 /// Takes a casm instruction, which can be constructed using the macro casm!, and
 /// returns its assembled representation.
 fn assemble_instruction(mut casm: CasmContext) -> InstructionRepr {
-    casm.instructions.remove(0).assemble()
+    casm.instructions.remove(0).assemble().unwrap()
 }
 
 #[test]
@@ -279,3 +286,24 @@ fn test_instruction_with_hint() {
         },
     );
 }
+
+#[test_case(
+    InstructionBody::AddAp(AddApInstruction { operand: ResOperand::from(1) }),
+    true;
+    "add_ap with ap++"
+)]
+#[test_case(CallInstruction::rel(3), true; "call with ap++")]
+#[test_case(InstructionBody::Ret(RetInstruction {}), true; "ret with ap++")]
+#[test_case(
+    InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+        state: CellRef { register: Register::FP, offset: -5 },
+        byte_count: CellRef { register: Register::FP, offset: -4 },
+        message: CellRef { register: Register::AP, offset: 3 },
+        finalize: false,
+    }),
+    false;
+    "blake2s without ap++"
+)]
+fn test_invalid_inc_ap_assemble(body: InstructionBody, inc_ap: bool) {
+    assert_eq!(Instruction::new(body, inc_ap).assemble(), Err(AssembleError::InvalidIncAp));
+}
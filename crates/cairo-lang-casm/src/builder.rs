@@ -19,7 +19,8 @@ use crate::deref_or_immediate;
 use crate::hints::Hint;
 use crate::instructions::{
     AddApInstruction, AssertEqInstruction, Blake2sCompressInstruction, CallInstruction,
-    Instruction, InstructionBody, JnzInstruction, JumpInstruction, RetInstruction,
+    Instruction, InstructionBody, JnzInstruction, JumpInstruction, QM31AssertEqInstruction,
+    RetInstruction,
 };
 use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
 
@@ -328,7 +329,7 @@ impl CasmBuilder {
         let instruction = self.next_instruction(
             match kind {
                 AssertEqKind::Felt252 => InstructionBody::AssertEq(inner),
-                AssertEqKind::QM31 => InstructionBody::QM31AssertEq(inner),
+                AssertEqKind::QM31 => InstructionBody::QM31AssertEq(QM31AssertEqInstruction(inner)),
             },
             true,
         );
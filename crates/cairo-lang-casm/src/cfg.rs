@@ -0,0 +1,186 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+use crate::instructions::{AddApInstruction, Instruction, InstructionBody};
+use crate::operand::ResOperand;
+use crate::program::{
+    instruction_offsets, program_size, relocate_branches, resolve_targets, static_branch_targets,
+};
+
+#[cfg(test)]
+#[path = "cfg_test.rs"]
+mod test;
+
+/// The way an instruction passes control to the instructions after it.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ControlFlow {
+    /// Control always passes to the next instruction.
+    Fallthrough,
+    /// Control always passes to the branch target.
+    Branch,
+    /// Control passes either to the next instruction or to the branch target.
+    ConditionalBranch,
+    /// Control passes to the call target, and returns to the next instruction.
+    Call,
+}
+
+impl InstructionBody {
+    /// Returns the way the instruction passes control to the following instructions.
+    pub fn control_flow(&self) -> ControlFlow {
+        match self {
+            InstructionBody::AddAp(_)
+            | InstructionBody::AssertEq(_)
+            | InstructionBody::QM31AssertEq(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => ControlFlow::Fallthrough,
+            InstructionBody::Jump(_) | InstructionBody::Ret(_) => ControlFlow::Branch,
+            InstructionBody::Jnz(_) => ControlFlow::ConditionalBranch,
+            InstructionBody::Call(_) => ControlFlow::Call,
+        }
+    }
+}
+
+/// A maximal sequence of instructions that is only entered at its first instruction and only
+/// left after its last one.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct BasicBlock {
+    /// The offset, in words, of the first instruction of the block.
+    pub start_offset: usize,
+    /// The indices of the instructions of the block.
+    pub instructions: Range<usize>,
+    /// The indices of the blocks control may pass to after the block. For a block ending with a
+    /// conditional branch or a call, the next block comes first, followed by the branch target.
+    pub successors: Vec<usize>,
+}
+
+/// The control flow graph of a program.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Cfg {
+    /// The basic blocks of the program, in program order.
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// Builds the control flow graph of a program.
+///
+/// Blocks start at the program start, at branch targets and right after branch instructions.
+/// Only the targets of relative branches with immediate targets are known statically, so other
+/// branches, as well as branches that do not land on an instruction, have no edge to their
+/// target.
+pub fn build_cfg(instructions: &[Instruction]) -> Cfg {
+    let offsets = instruction_offsets(instructions);
+    // The index of the target instruction of each resolvable branch, by branch index.
+    let mut branch_targets = vec![None; instructions.len()];
+    let mut is_leader = vec![false; instructions.len()];
+    for resolved in resolve_targets(instructions, 0) {
+        if let Ok(target) = resolved.target {
+            let target_index = offsets.binary_search(&target).unwrap();
+            branch_targets[resolved.index] = Some(target_index);
+            is_leader[target_index] = true;
+        }
+    }
+    if let Some(first) = is_leader.first_mut() {
+        *first = true;
+    }
+    for (index, instruction) in instructions.iter().enumerate() {
+        if instruction.body.control_flow() != ControlFlow::Fallthrough {
+            if let Some(next) = is_leader.get_mut(index + 1) {
+                *next = true;
+            }
+        }
+    }
+
+    let starts: Vec<usize> = (0..instructions.len()).filter(|index| is_leader[*index]).collect();
+    let block_of = |index: usize| starts.binary_search(&index).unwrap();
+    let blocks = starts
+        .iter()
+        .enumerate()
+        .map(|(block_index, start)| {
+            let end = starts.get(block_index + 1).copied().unwrap_or(instructions.len());
+            let last = end - 1;
+            let next = (end < instructions.len()).then_some(block_index + 1);
+            let target = branch_targets[last].map(block_of);
+            let successors = match instructions[last].body.control_flow() {
+                ControlFlow::Fallthrough => next.into_iter().collect(),
+                ControlFlow::Branch => target.into_iter().collect(),
+                ControlFlow::ConditionalBranch | ControlFlow::Call => {
+                    next.into_iter().chain(target).collect()
+                }
+            };
+            BasicBlock { start_offset: offsets[*start], instructions: *start..end, successors }
+        })
+        .collect();
+    Cfg { blocks }
+}
+
+/// Returns the indices of the `ap += imm` instructions inside the loops of the program, whose ap
+/// change is the same on every iteration, in program order.
+///
+/// A loop is detected as a backward jump or jnz from the end of a block, and is taken to consist
+/// of the blocks from the target of the branch up to the branching block. `cfg` must be the
+/// control flow graph of `instructions`, as built by [build_cfg].
+pub fn loop_invariant_addaps(cfg: &Cfg, instructions: &[Instruction]) -> Vec<usize> {
+    let mut in_loop = vec![false; instructions.len()];
+    for (block_index, block) in cfg.blocks.iter().enumerate() {
+        let last = &instructions[block.instructions.end - 1];
+        if !matches!(last.body.control_flow(), ControlFlow::Branch | ControlFlow::ConditionalBranch)
+        {
+            continue;
+        }
+        for successor in &block.successors {
+            if *successor <= block_index {
+                let start = cfg.blocks[*successor].instructions.start;
+                in_loop[start..block.instructions.end].fill(true);
+            }
+        }
+    }
+    instructions
+        .iter()
+        .enumerate()
+        .filter(|(index, instruction)| {
+            in_loop[*index]
+                && matches!(
+                    &instruction.body,
+                    InstructionBody::AddAp(AddApInstruction { operand: ResOperand::Immediate(_) })
+                )
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Removes the instructions that are unreachable from the start of the program, and patches the
+/// offsets of the relative branches of the remaining instructions.
+///
+/// The targets of calls are reachable, as they are successors of their calling blocks. If the
+/// targets of some branch are not known statically, the program is left unchanged, as any
+/// instruction may be such a target.
+pub fn eliminate_unreachable(instructions: &mut Vec<Instruction>) {
+    let offsets = instruction_offsets(instructions);
+    if static_branch_targets(instructions, &offsets, program_size(instructions)).is_none() {
+        return;
+    }
+    let cfg = build_cfg(instructions);
+    let mut reachable = vec![false; cfg.blocks.len()];
+    let mut pending: Vec<usize> = if cfg.blocks.is_empty() { vec![] } else { vec![0] };
+    while let Some(block) = pending.pop() {
+        if !core::mem::replace(&mut reachable[block], true) {
+            pending.extend(&cfg.blocks[block].successors);
+        }
+    }
+
+    let is_kept: Vec<bool> = cfg
+        .blocks
+        .iter()
+        .zip(reachable)
+        .flat_map(|(block, is_reachable)| block.instructions.clone().map(move |_| is_reachable))
+        .collect();
+    let kept = offsets
+        .into_iter()
+        .zip(instructions.iter().cloned())
+        .zip(is_kept)
+        .filter_map(|(instruction, is_kept)| is_kept.then_some(instruction))
+        .collect();
+    if let Some(relocated) = relocate_branches(kept) {
+        *instructions = relocated;
+    }
+}
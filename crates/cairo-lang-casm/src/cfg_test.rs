@@ -0,0 +1,147 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec};
+use core::fmt::Write;
+
+use indoc::indoc;
+use pretty_assertions::assert_eq;
+use test_case::test_case;
+use test_log::test;
+
+use super::{
+    BasicBlock, Cfg, ControlFlow, build_cfg, eliminate_unreachable, loop_invariant_addaps,
+};
+use crate::casm;
+use crate::instructions::{AssertEqInstruction, InstructionBody, QM31AssertEqInstruction};
+use crate::operand::{CellRef, DerefOrImmediate, Register};
+
+#[test_case("ap += 1", ControlFlow::Fallthrough; "add_ap")]
+#[test_case("[ap + 0] = [fp + -3]", ControlFlow::Fallthrough; "assert_eq")]
+#[test_case(
+    "blake2s[state=[fp + -5], message=[fp + -3], byte_count=[fp + -4], finalize=false] => [ap + 0]",
+    ControlFlow::Fallthrough;
+    "blake2s"
+)]
+#[test_case(
+    "keccak[state=[fp + -5], message=[fp + -3], byte_count=[fp + -4], finalize=true] => [ap + 0]",
+    ControlFlow::Fallthrough;
+    "keccak"
+)]
+#[test_case("jmp rel 2", ControlFlow::Branch; "jump")]
+#[test_case("ret", ControlFlow::Branch; "ret")]
+#[test_case("jmp rel 2 if [ap + -1] != 0", ControlFlow::ConditionalBranch; "jnz")]
+#[test_case("call rel 4", ControlFlow::Call; "call")]
+fn test_control_flow(body: &str, expected: ControlFlow) {
+    assert_eq!(body.parse::<InstructionBody>().unwrap().control_flow(), expected);
+}
+
+#[test]
+fn test_qm31_control_flow() {
+    let cell = |offset| CellRef { register: Register::FP, offset };
+    let body = AssertEqInstruction::add(cell(1), cell(-3), DerefOrImmediate::from(cell(-4)));
+    let InstructionBody::AssertEq(insn) = body else { unreachable!() };
+    assert_eq!(
+        InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)).control_flow(),
+        ControlFlow::Fallthrough
+    );
+}
+
+#[test]
+fn test_build_cfg() {
+    let instructions = casm! {
+        // Block 0.
+        [ap + 0] = 10, ap++;
+        // Block 1: the loop body.
+        [ap + 0] = [ap + -1] + (-1), ap++;
+        jmp rel (-2) if [ap + -1] != 0;
+        // Block 2.
+        call rel 3;
+        // Block 3.
+        ret;
+        // Block 4: the called function.
+        [ap + 0] = [fp + -3], ap++;
+        jmp abs [fp + -4];
+        // Block 5: unreachable.
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        build_cfg(&instructions),
+        Cfg {
+            blocks: vec![
+                BasicBlock { start_offset: 0, instructions: 0..1, successors: vec![1] },
+                BasicBlock { start_offset: 2, instructions: 1..3, successors: vec![2, 1] },
+                BasicBlock { start_offset: 6, instructions: 3..4, successors: vec![3, 4] },
+                BasicBlock { start_offset: 8, instructions: 4..5, successors: vec![] },
+                BasicBlock { start_offset: 9, instructions: 5..7, successors: vec![] },
+                BasicBlock { start_offset: 11, instructions: 7..8, successors: vec![] },
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_eliminate_unreachable() {
+    let mut instructions = casm! {
+        jmp rel 9 if [fp + -3] != 0;
+        call rel 9;
+        jmp rel 14;
+        // Unreachable.
+        [ap + 0] = 2, ap++;
+        ret;
+        // The jnz target.
+        [ap + 0] = 3, ap++;
+        // The call target.
+        [ap + 0] = 4, ap++;
+        ret;
+        // Unreachable.
+        [ap + 0] = 5, ap++;
+        jmp rel (-5);
+        // The jmp target.
+        ret;
+    }
+    .instructions;
+    eliminate_unreachable(&mut instructions);
+    assert_eq!(
+        instructions.iter().fold(String::new(), |mut text, instruction| {
+            writeln!(text, "{instruction};").unwrap();
+            text
+        }),
+        indoc! {"
+            jmp rel 6 if [fp + -3] != 0;
+            call rel 6;
+            jmp rel 7;
+            [ap + 0] = 3, ap++;
+            [ap + 0] = 4, ap++;
+            ret;
+            ret;
+        "}
+    );
+}
+
+#[test]
+fn test_eliminate_unreachable_unknown_targets() {
+    let mut instructions = casm! {
+        jmp abs [fp + -3];
+        ret;
+    }
+    .instructions;
+    let original = instructions.clone();
+    eliminate_unreachable(&mut instructions);
+    assert_eq!(instructions, original);
+}
+
+#[test]
+fn test_loop_invariant_addaps() {
+    let instructions = casm! {
+        ap += 1;
+        // Offset 2: the loop body.
+        ap += 2;
+        ap += [fp + -3];
+        [ap + 0] = [ap + -3] + (-1), ap++;
+        jmp rel (-5) if [ap + -1] != 0;
+        ap += 1;
+        ret;
+    }
+    .instructions;
+    assert_eq!(loop_invariant_addaps(&build_cfg(&instructions), &instructions), [1]);
+}
@@ -0,0 +1,116 @@
+//! Tracking of the cells whose values are known to be constants, through the `assert_eq`
+//! instructions of straight-line code.
+
+use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use crate::instructions::{Instruction, InstructionBody};
+use crate::operand::{CellRef, Register, ResOperand};
+
+#[cfg(test)]
+#[path = "const_env_test.rs"]
+mod test;
+
+/// A memory cell, identified independently of the changes of ap.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+enum Slot {
+    /// The cell at the given offset from the value ap had when tracking started, or was last
+    /// reset.
+    Ap(i64),
+    /// The cell at the given offset from fp.
+    Fp(i16),
+}
+
+/// The cells known to hold constant values after running a sequence of instructions.
+///
+/// Values are learned from `assert_eq` instructions over the base field, where one side is an
+/// immediate or a cell holding a known value. Memory is write-once, so a learned value stays
+/// valid, but the cells are kept relative to ap, which is tracked as long as its changes are
+/// known.
+///
+/// The instructions are taken to run in sequence, so they should form a basic block, as given by
+/// [crate::cfg::build_cfg]. Calls are assumed to return to the same frame, with an unknown ap.
+#[derive(Clone, Debug, Default)]
+pub struct ConstEnv {
+    /// The known values of the cells.
+    values: OrderedHashMap<Slot, BigInt>,
+    /// The change of ap since tracking started or was last reset.
+    ap_change: i64,
+}
+impl ConstEnv {
+    /// Returns the values known after running the given instructions.
+    pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        let mut env = Self::default();
+        for instruction in instructions {
+            env.apply(instruction);
+        }
+        env
+    }
+
+    /// Updates the known values to the state after running `instruction`.
+    pub fn apply(&mut self, instruction: &Instruction) {
+        let ap_update = match &instruction.body {
+            InstructionBody::AssertEq(insn) => {
+                match (self.value(insn.a).cloned(), self.resolve(&insn.b)) {
+                    (None, Some(value)) => {
+                        self.insert(insn.a, value);
+                    }
+                    (Some(value), None) => {
+                        if let ResOperand::Deref(cell) = insn.b {
+                            self.insert(cell, value);
+                        }
+                    }
+                    _ => {}
+                }
+                Some(0)
+            }
+            InstructionBody::AddAp(insn) => self.resolve(&insn.operand).and_then(|n| n.to_i64()),
+            InstructionBody::Call(_) => None,
+            InstructionBody::QM31AssertEq(_)
+            | InstructionBody::Jump(_)
+            | InstructionBody::Jnz(_)
+            | InstructionBody::Ret(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => Some(0),
+        };
+        match ap_update {
+            Some(change) => self.ap_change += change + i64::from(instruction.inc_ap),
+            None => {
+                self.values.retain(|slot, _| matches!(slot, Slot::Fp(_)));
+                self.ap_change = 0;
+            }
+        }
+    }
+
+    /// Returns the known value of `cell`, relative to the current ap and fp.
+    pub fn value(&self, cell: CellRef) -> Option<&BigInt> {
+        self.values.get(&self.slot(cell))
+    }
+
+    /// Returns the known value of `operand`, relative to the current ap and fp.
+    ///
+    /// Only immediates and derefs of cells with known values are resolved, as the value of a
+    /// binary operation depends on the prime.
+    pub fn resolve(&self, operand: &ResOperand) -> Option<BigInt> {
+        match operand {
+            ResOperand::Immediate(imm) => Some(imm.value.clone()),
+            ResOperand::Deref(cell) => self.value(*cell).cloned(),
+            ResOperand::DoubleDeref(_, _) | ResOperand::BinOp(_) => None,
+        }
+    }
+
+    /// Records that `cell`, relative to the current ap and fp, holds `value`.
+    fn insert(&mut self, cell: CellRef, value: BigInt) {
+        let slot = self.slot(cell);
+        self.values.insert(slot, value);
+    }
+
+    /// Returns the slot of `cell`, relative to the current ap and fp.
+    fn slot(&self, cell: CellRef) -> Slot {
+        match cell.register {
+            Register::AP => Slot::Ap(self.ap_change + i64::from(cell.offset)),
+            Register::FP => Slot::Fp(cell.offset),
+        }
+    }
+}
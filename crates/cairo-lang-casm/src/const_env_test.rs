@@ -0,0 +1,55 @@
+use num_bigint::BigInt;
+use test_log::test;
+
+use super::ConstEnv;
+use crate::casm;
+use crate::operand::{CellRef, Register, ResOperand};
+
+#[test]
+fn test_const_env() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let instructions = casm! {
+        [ap + 0] = 5, ap++;
+        [fp + 1] = [ap + -1];
+        ap += 2;
+        [ap + 0] = [fp + -3], ap++;
+        [ap + -4] = [fp + 2];
+        [ap + 0] = [fp + 1] + 3;
+    }
+    .instructions;
+    let mut env = ConstEnv::default();
+    for instruction in &instructions[..5] {
+        env.apply(instruction);
+    }
+    let five = BigInt::from(5);
+    // The value written by the first instruction, relative to the current ap.
+    assert_eq!(env.value(ap(-4)), Some(&five));
+    assert_eq!(env.value(fp(1)), Some(&five));
+    // Learned from the other side of the assertion.
+    assert_eq!(env.value(fp(2)), Some(&five));
+    assert_eq!(env.value(ap(-1)), None);
+    assert_eq!(env.value(fp(-3)), None);
+    assert_eq!(env.resolve(&ResOperand::Deref(fp(1))), Some(five.clone()));
+    assert_eq!(env.resolve(&ResOperand::from(7)), Some(BigInt::from(7)));
+
+    let ConstEnv { values, ap_change } = ConstEnv::from_instructions(&instructions);
+    assert_eq!(values.len(), 3);
+    assert_eq!(ap_change, 4);
+}
+
+#[test]
+fn test_const_env_unknown_ap_change() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let env = ConstEnv::from_instructions(
+        &casm! {
+            [ap + 0] = 5, ap++;
+            [fp + 0] = 6;
+            call rel 4;
+        }
+        .instructions,
+    );
+    assert_eq!(env.value(ap(-1)), None);
+    assert_eq!(env.value(fp(0)), Some(&BigInt::from(6)));
+}
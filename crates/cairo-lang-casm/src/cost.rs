@@ -0,0 +1,48 @@
+//! A rough estimation of the resources used by the execution of instructions, for cases where
+//! running the VM is not an option.
+//!
+//! Every instruction takes a single step. The memory cells an instruction touches are the cells of
+//! its operands, as given by [InstructionBody::reads] and [InstructionBody::writes]:
+//!
+//! | Instruction        | Memory cells                                   | Builtin usage |
+//! |--------------------|------------------------------------------------|---------------|
+//! | `ap += x`          | The cells of `x`                               |               |
+//! | `a = b`            | `a` and the cells of `b`                       |               |
+//! | `call`             | The old fp and return address, and the target  |               |
+//! | `jmp`              | The target                                     |               |
+//! | `jnz`              | The condition and the offset                   |               |
+//! | `ret`              | The previous fp and the return address         |               |
+//! | `blake2s`          | The three operands and the output pointer      | blake2s       |
+//! | `keccak`           | The three operands and the output pointer      | keccak        |
+//!
+//! The words of the instructions themselves, and the cells accessed through the pointers given to
+//! the hash instructions, are not counted.
+
+use crate::instructions::InstructionBody;
+
+#[cfg(test)]
+#[path = "cost_test.rs"]
+mod test;
+
+/// The estimated resources used by the execution of an instruction.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StepCost {
+    pub steps: usize,
+    pub memory_cells: usize,
+    /// The number of blake2s compressions.
+    pub blake2s: usize,
+    /// The number of keccak permutations.
+    pub keccak: usize,
+}
+
+impl InstructionBody {
+    /// Returns the estimated resources used by the execution of the instruction.
+    pub fn step_cost(&self) -> StepCost {
+        StepCost {
+            steps: 1,
+            memory_cells: self.reads().len() + self.writes().len(),
+            blake2s: usize::from(matches!(self, InstructionBody::Blake2sCompress(_))),
+            keccak: usize::from(matches!(self, InstructionBody::Keccak(_))),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use test_log::test;
+
+use super::StepCost;
+use crate::instructions::{AssertEqInstruction, Blake2sCompressInstruction, InstructionBody};
+use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+
+#[test]
+fn test_step_cost() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+
+    let blake = InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+        state: fp(-5),
+        byte_count: fp(-4),
+        message: ap(3),
+        finalize: false,
+    });
+    assert_eq!(blake.step_cost(), StepCost { steps: 1, memory_cells: 4, blake2s: 1, keccak: 0 });
+
+    let assert_eq = InstructionBody::AssertEq(AssertEqInstruction {
+        a: ap(0),
+        b: ResOperand::BinOp(BinOpOperand {
+            op: Operation::Mul,
+            a: fp(-3),
+            b: DerefOrImmediate::Deref(ap(-1)),
+        }),
+    });
+    assert_eq!(
+        assert_eq.step_cost(),
+        StepCost { steps: 1, memory_cells: 3, blake2s: 0, keccak: 0 }
+    );
+
+    let assert_eq_imm =
+        InstructionBody::AssertEq(AssertEqInstruction { a: ap(0), b: ResOperand::from(5) });
+    assert_eq!(
+        assert_eq_imm.step_cost(),
+        StepCost { steps: 1, memory_cells: 1, blake2s: 0, keccak: 0 }
+    );
+}
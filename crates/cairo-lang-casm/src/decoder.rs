@@ -0,0 +1,376 @@
+use core::fmt::Display;
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use crate::assembler::{
+    ApUpdate, FpUpdate, InstructionRepr, Op1Addr, Opcode, OpcodeExtension, PcUpdate, Res,
+};
+use crate::instructions::{
+    AddApInstruction, AssertEqInstruction, Blake2sCompressInstruction, CallInstruction,
+    Instruction, InstructionBody, JnzInstruction, JumpInstruction, QM31AssertEqInstruction,
+    RetInstruction,
+};
+use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+
+#[cfg(test)]
+#[path = "decoder_test.rs"]
+mod test;
+
+const OFFSET_BITS: u32 = 16;
+const FLAGS_OFFSET: u32 = 3 * OFFSET_BITS;
+const OPCODE_EXT_OFFSET: u32 = 63;
+
+const DST_REG_BIT: u32 = 0;
+const OP0_REG_BIT: u32 = 1;
+const OP1_IMM_BIT: u32 = 2;
+const OP1_FP_BIT: u32 = 3;
+const OP1_AP_BIT: u32 = 4;
+const RES_ADD_BIT: u32 = 5;
+const RES_MUL_BIT: u32 = 6;
+const PC_JUMP_ABS_BIT: u32 = 7;
+const PC_JUMP_REL_BIT: u32 = 8;
+const PC_JNZ_BIT: u32 = 9;
+const AP_ADD_BIT: u32 = 10;
+const AP_ADD1_BIT: u32 = 11;
+const OPCODE_CALL_BIT: u32 = 12;
+const OPCODE_RET_BIT: u32 = 13;
+const OPCODE_ASSERT_EQ_BIT: u32 = 14;
+
+/// An error encountered while decoding an instruction from its encoded words.
+#[derive(Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// No words were given to decode.
+    EmptyInput,
+    /// The instruction word is negative or too large to be an instruction.
+    WordOutOfRange,
+    /// The opcode extension of the instruction word is unknown.
+    UnknownOpcodeExtension,
+    /// More than one flag of the given mutually exclusive flag group is set.
+    ConflictingFlags(&'static str),
+    /// The instruction has an immediate operand, but no word follows it.
+    MissingImmediate,
+    /// The flags and offsets are individually valid, but do not describe any instruction.
+    InvalidFlagCombination,
+}
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::EmptyInput => write!(f, "No words to decode"),
+            DecodeError::WordOutOfRange => write!(f, "Instruction word out of range"),
+            DecodeError::UnknownOpcodeExtension => write!(f, "Unknown opcode extension"),
+            DecodeError::ConflictingFlags(group) => write!(f, "Conflicting {group} flags"),
+            DecodeError::MissingImmediate => write!(f, "Missing immediate word"),
+            DecodeError::InvalidFlagCombination => write!(f, "Invalid flag combination"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Decodes a single instruction body from the start of `words`.
+/// Returns the body and the number of words consumed.
+pub fn decode(words: &[BigInt]) -> Result<(InstructionBody, usize), DecodeError> {
+    decode_instruction(words).map(|(instruction, size)| (instruction.body, size))
+}
+
+/// Decodes a single instruction, including its ap++ flag, from the start of `words`.
+/// Returns the instruction (without hints) and the number of words consumed.
+pub fn decode_instruction(words: &[BigInt]) -> Result<(Instruction, usize), DecodeError> {
+    let (repr, size) = InstructionRepr::decode(words)?;
+    Ok((repr.disassemble()?, size))
+}
+
+/// Same as [decode_instruction], for words encoded as elements of the field of the given prime by
+/// [Instruction::encode_mod].
+///
+/// An immediate in the upper half of the field is decoded as the negative value it represents,
+/// so that offsets such as `jmp rel -5` are recovered.
+pub fn decode_instruction_mod(
+    words: &[BigInt],
+    prime: &BigInt,
+) -> Result<(Instruction, usize), DecodeError> {
+    let (mut repr, size) = InstructionRepr::decode(words)?;
+    if let Some(imm) = &mut repr.imm {
+        if *imm > prime / 2 {
+            *imm -= prime;
+        }
+    }
+    Ok((repr.disassemble()?, size))
+}
+
+/// Returns the index of the single set bit out of `bits` in `flags`, or `None` if none is set.
+fn exclusive_flag(
+    flags: u64,
+    bits: &[u32],
+    group: &'static str,
+) -> Result<Option<u32>, DecodeError> {
+    let mut set_bits = bits.iter().filter(|bit| flags & (1 << **bit) != 0);
+    let bit = set_bits.next().copied();
+    if set_bits.next().is_some() { Err(DecodeError::ConflictingFlags(group)) } else { Ok(bit) }
+}
+
+/// Converts an encoded offset in the range [0, 2^16) back into the range [-2^15, 2^15).
+fn decode_offset(word: u64, index: u32) -> i16 {
+    let biased = (word >> (index * OFFSET_BITS)) & ((1 << OFFSET_BITS) - 1);
+    (biased as i32 - (1 << (OFFSET_BITS - 1))) as i16
+}
+
+fn register_from_flag(is_fp: bool) -> Register {
+    if is_fp { Register::FP } else { Register::AP }
+}
+
+/// The flags of an instruction, describing how its operands are computed and how the registers
+/// are updated.
+#[derive(Debug, Eq, PartialEq)]
+pub struct InstructionFlags {
+    pub dst_register: Register,
+    pub op0_register: Register,
+    pub op1_addr: Op1Addr,
+    pub res: Res,
+    pub pc_update: PcUpdate,
+    pub ap_update: ApUpdate,
+    pub opcode: Opcode,
+    pub opcode_extension: OpcodeExtension,
+}
+impl InstructionFlags {
+    /// Decodes the flags from the flags part of an instruction word.
+    fn decode(flags: u64, opcode_extension: OpcodeExtension) -> Result<Self, DecodeError> {
+        let op1_addr =
+            match exclusive_flag(flags, &[OP1_IMM_BIT, OP1_FP_BIT, OP1_AP_BIT], "op1_addr")? {
+                Some(OP1_IMM_BIT) => Op1Addr::Imm,
+                Some(OP1_FP_BIT) => Op1Addr::FP,
+                Some(_) => Op1Addr::AP,
+                None => Op1Addr::Op0,
+            };
+        let pc_update =
+            match exclusive_flag(flags, &[PC_JUMP_ABS_BIT, PC_JUMP_REL_BIT, PC_JNZ_BIT], "pc")? {
+                Some(PC_JUMP_ABS_BIT) => PcUpdate::Jump,
+                Some(PC_JUMP_REL_BIT) => PcUpdate::JumpRel,
+                Some(_) => PcUpdate::Jnz,
+                None => PcUpdate::Regular,
+            };
+        let res = match exclusive_flag(flags, &[RES_ADD_BIT, RES_MUL_BIT], "res")? {
+            Some(RES_ADD_BIT) => Res::Add,
+            Some(_) => Res::Mul,
+            None if pc_update == PcUpdate::Jnz => Res::Unconstrained,
+            None => Res::Op1,
+        };
+        let opcode = match exclusive_flag(
+            flags,
+            &[OPCODE_CALL_BIT, OPCODE_RET_BIT, OPCODE_ASSERT_EQ_BIT],
+            "opcode",
+        )? {
+            Some(OPCODE_CALL_BIT) => Opcode::Call,
+            Some(OPCODE_RET_BIT) => Opcode::Ret,
+            Some(_) => Opcode::AssertEq,
+            None => Opcode::Nop,
+        };
+        let ap_update = match exclusive_flag(flags, &[AP_ADD_BIT, AP_ADD1_BIT], "ap")? {
+            Some(AP_ADD_BIT) => ApUpdate::Add,
+            Some(_) => ApUpdate::Add1,
+            None if opcode == Opcode::Call => ApUpdate::Add2,
+            None => ApUpdate::Regular,
+        };
+        Ok(Self {
+            dst_register: register_from_flag(flags & (1 << DST_REG_BIT) != 0),
+            op0_register: register_from_flag(flags & (1 << OP0_REG_BIT) != 0),
+            op1_addr,
+            res,
+            pc_update,
+            ap_update,
+            opcode,
+            opcode_extension,
+        })
+    }
+}
+
+/// The offsets of an instruction.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Offsets {
+    pub off0: i16,
+    pub off1: i16,
+    pub off2: i16,
+}
+
+impl InstructionBody {
+    /// Lifts the flags, offsets and immediate of an instruction into an instruction body.
+    ///
+    /// Only accepts combinations that are produced by [Instruction::assemble]. The ap++ flag,
+    /// which is part of `flags.ap_update`, is not part of the body; use
+    /// [InstructionRepr::disassemble] to keep it.
+    pub fn from_flags(
+        flags: InstructionFlags,
+        offsets: Offsets,
+        imm: Option<BigInt>,
+    ) -> Result<InstructionBody, DecodeError> {
+        match (flags.op1_addr == Op1Addr::Imm, imm.is_some()) {
+            (true, false) => return Err(DecodeError::MissingImmediate),
+            (false, true) => return Err(DecodeError::InvalidFlagCombination),
+            _ => {}
+        }
+        Ok(InstructionRepr::from_flags(flags, offsets, imm).disassemble()?.body)
+    }
+}
+
+impl InstructionRepr {
+    /// Decodes the low level representation of the instruction at the start of `words`.
+    /// Returns the representation and the number of words consumed.
+    pub fn decode(words: &[BigInt]) -> Result<(InstructionRepr, usize), DecodeError> {
+        let word = words.first().ok_or(DecodeError::EmptyInput)?;
+        let word = word.to_u128().ok_or(DecodeError::WordOutOfRange)?;
+        let opcode_extension = match word >> OPCODE_EXT_OFFSET {
+            0 => OpcodeExtension::Stone,
+            1 => OpcodeExtension::Blake2s,
+            2 => OpcodeExtension::Blake2sFinalize,
+            3 => OpcodeExtension::QM31,
+            _ => return Err(DecodeError::UnknownOpcodeExtension),
+        };
+        let word = (word & ((1 << OPCODE_EXT_OFFSET) - 1)) as u64;
+        let flags = InstructionFlags::decode(word >> FLAGS_OFFSET, opcode_extension)?;
+        let offsets = Offsets {
+            off0: decode_offset(word, 0),
+            off1: decode_offset(word, 1),
+            off2: decode_offset(word, 2),
+        };
+        let (imm, size) = if flags.op1_addr == Op1Addr::Imm {
+            (Some(words.get(1).ok_or(DecodeError::MissingImmediate)?.clone()), 2)
+        } else {
+            (None, 1)
+        };
+        Ok((InstructionRepr::from_flags(flags, offsets, imm), size))
+    }
+
+    /// Builds the low level representation from its parts, deriving the fp update from the
+    /// opcode.
+    fn from_flags(flags: InstructionFlags, offsets: Offsets, imm: Option<BigInt>) -> Self {
+        let fp_update = match flags.opcode {
+            Opcode::Nop | Opcode::AssertEq => FpUpdate::Regular,
+            Opcode::Call => FpUpdate::ApPlus2,
+            Opcode::Ret => FpUpdate::Dst,
+        };
+        InstructionRepr {
+            off0: offsets.off0,
+            off1: offsets.off1,
+            off2: offsets.off2,
+            imm,
+            dst_register: flags.dst_register,
+            op0_register: flags.op0_register,
+            op1_addr: flags.op1_addr,
+            res: flags.res,
+            pc_update: flags.pc_update,
+            ap_update: flags.ap_update,
+            fp_update,
+            opcode: flags.opcode,
+            opcode_extension: flags.opcode_extension,
+        }
+    }
+
+    /// Lifts the low level representation back into an instruction (without hints).
+    ///
+    /// Only accepts representations that are produced by [Instruction::assemble].
+    pub fn disassemble(&self) -> Result<Instruction, DecodeError> {
+        let inc_ap = self.ap_update == ApUpdate::Add1;
+        let body = match (&self.opcode_extension, &self.opcode, &self.pc_update) {
+            (OpcodeExtension::Blake2s | OpcodeExtension::Blake2sFinalize, Opcode::Nop, _)
+                if inc_ap =>
+            {
+                InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+                    state: CellRef { register: self.op0_register, offset: self.off1 },
+                    byte_count: CellRef { register: self.dst_register, offset: self.off0 },
+                    message: CellRef { register: self.op1_register()?, offset: self.off2 },
+                    finalize: self.opcode_extension == OpcodeExtension::Blake2sFinalize,
+                })
+            }
+            (OpcodeExtension::Stone, Opcode::AssertEq, _) => {
+                InstructionBody::AssertEq(self.assert_eq_instruction()?)
+            }
+            (OpcodeExtension::QM31, Opcode::AssertEq, _) => InstructionBody::QM31AssertEq(
+                QM31AssertEqInstruction(self.assert_eq_instruction()?),
+            ),
+            (OpcodeExtension::Stone, Opcode::Call, PcUpdate::Jump | PcUpdate::JumpRel)
+                if self.ap_update == ApUpdate::Add2 =>
+            {
+                InstructionBody::Call(CallInstruction {
+                    target: self.op1_deref_or_immediate()?,
+                    relative: self.pc_update == PcUpdate::JumpRel,
+                })
+            }
+            (OpcodeExtension::Stone, Opcode::Ret, _) if self.ap_update == ApUpdate::Regular => {
+                InstructionBody::Ret(RetInstruction {})
+            }
+            (OpcodeExtension::Stone, Opcode::Nop, PcUpdate::Jump | PcUpdate::JumpRel) => {
+                InstructionBody::Jump(JumpInstruction {
+                    target: self.op1_deref_or_immediate()?,
+                    relative: self.pc_update == PcUpdate::JumpRel,
+                })
+            }
+            (OpcodeExtension::Stone, Opcode::Nop, PcUpdate::Jnz) => {
+                InstructionBody::Jnz(JnzInstruction {
+                    jump_offset: self.op1_deref_or_immediate()?,
+                    condition: CellRef { register: self.dst_register, offset: self.off0 },
+                })
+            }
+            (OpcodeExtension::Stone, Opcode::Nop, PcUpdate::Regular)
+                if self.ap_update == ApUpdate::Add =>
+            {
+                InstructionBody::AddAp(AddApInstruction { operand: self.res_operand()? })
+            }
+            _ => return Err(DecodeError::InvalidFlagCombination),
+        };
+        // The guards above ensure that assembling the instruction does not panic.
+        let instruction = Instruction::new(body, inc_ap);
+        // Any field that was ignored while lifting must match its canonical value.
+        if instruction.assemble().as_ref() != Ok(self) {
+            return Err(DecodeError::InvalidFlagCombination);
+        }
+        Ok(instruction)
+    }
+
+    /// Returns the register op1 is based on, if it is based on ap or fp.
+    fn op1_register(&self) -> Result<Register, DecodeError> {
+        match self.op1_addr {
+            Op1Addr::AP => Ok(Register::AP),
+            Op1Addr::FP => Ok(Register::FP),
+            Op1Addr::Imm | Op1Addr::Op0 => Err(DecodeError::InvalidFlagCombination),
+        }
+    }
+
+    fn op1_deref_or_immediate(&self) -> Result<DerefOrImmediate, DecodeError> {
+        match &self.imm {
+            Some(imm) => Ok(DerefOrImmediate::Immediate(imm.clone().into())),
+            None => Ok(DerefOrImmediate::Deref(CellRef {
+                register: self.op1_register()?,
+                offset: self.off2,
+            })),
+        }
+    }
+
+    fn res_operand(&self) -> Result<ResOperand, DecodeError> {
+        let op = match self.res {
+            Res::Op1 if self.op1_addr == Op1Addr::Op0 => {
+                return Ok(ResOperand::DoubleDeref(
+                    CellRef { register: self.op0_register, offset: self.off1 },
+                    self.off2,
+                ));
+            }
+            Res::Op1 => return Ok(self.op1_deref_or_immediate()?.into()),
+            Res::Add => Operation::Add,
+            Res::Mul => Operation::Mul,
+            Res::Unconstrained => return Err(DecodeError::InvalidFlagCombination),
+        };
+        Ok(ResOperand::BinOp(BinOpOperand {
+            op,
+            a: CellRef { register: self.op0_register, offset: self.off1 },
+            b: self.op1_deref_or_immediate()?,
+        }))
+    }
+
+    fn assert_eq_instruction(&self) -> Result<AssertEqInstruction, DecodeError> {
+        Ok(AssertEqInstruction {
+            a: CellRef { register: self.dst_register, offset: self.off0 },
+            b: self.res_operand()?,
+        })
+    }
+}
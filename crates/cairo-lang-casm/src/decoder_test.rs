@@ -0,0 +1,254 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use num_bigint::BigInt;
+use pretty_assertions::assert_eq;
+use test_case::test_case;
+use test_log::test;
+
+use super::{
+    DecodeError, InstructionFlags, Offsets, decode, decode_instruction, decode_instruction_mod,
+};
+use crate::assembler::{ApUpdate, Op1Addr, Opcode, OpcodeExtension, PcUpdate, Res};
+use crate::casm;
+use crate::instructions::{
+    AssertEqInstruction, Blake2sCompressInstruction, Instruction, InstructionBody, JumpInstruction,
+};
+use crate::operand::{CellRef, DerefOrImmediate, Register};
+
+#[test]
+fn test_decode_round_trip() {
+    let ctx = casm! {
+        [fp + -5] = 1, ap++;
+        [fp + -5] = [ap + 1] + [fp + -5], ap++;
+        [fp + 5] = [ap + 1] * 2;
+        [ap + 5] = [[ap + 1] + -2];
+        [ap + 5] = [fp + 3];
+        call rel 3;
+        call abs [fp + 2];
+        jmp rel -5, ap++;
+        jmp abs [ap + 1];
+        jmp rel 205 if [ap + 5] != 0;
+        jmp rel [fp + -19] if [ap + 17] != 0, ap++;
+        ap += [fp + 4];
+        ap += 7;
+        ret;
+    };
+    let mut instructions = ctx.instructions;
+    instructions.push(Instruction::new(
+        InstructionBody::QM31AssertEq(
+            crate::instructions::AssertEqInstruction {
+                a: CellRef { register: Register::AP, offset: 0 },
+                b: crate::res!([fp + -4] * [ap + -1]),
+            }
+            .into(),
+        ),
+        true,
+    ));
+    for finalize in [false, true] {
+        instructions.push(Instruction::new(
+            InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+                state: CellRef { register: Register::FP, offset: -5 },
+                byte_count: CellRef { register: Register::FP, offset: -4 },
+                message: CellRef { register: Register::AP, offset: 3 },
+                finalize,
+            }),
+            true,
+        ));
+    }
+
+    for instruction in instructions {
+        let encoded = instruction.encode().unwrap();
+        assert_eq!(decode(&encoded), Ok((instruction.body.clone(), instruction.body.op_size())));
+        assert_eq!(decode_instruction(&encoded), Ok((instruction, encoded.len())));
+    }
+}
+
+#[test]
+fn test_decode_program() {
+    let ctx = casm! {
+        [ap + 0] = 1, ap++;
+        [ap + 0] = [fp + -5], ap++;
+        call rel 3;
+        ret;
+    };
+    let words: Vec<BigInt> =
+        ctx.instructions.iter().flat_map(|instruction| instruction.encode().unwrap()).collect();
+    let mut decoded = vec![];
+    let mut words = words.as_slice();
+    while !words.is_empty() {
+        let (instruction, size) = decode_instruction(words).unwrap();
+        decoded.push(instruction);
+        words = &words[size..];
+    }
+    assert_eq!(decoded, ctx.instructions);
+}
+
+#[test_case(vec![], DecodeError::EmptyInput; "empty")]
+#[test_case(vec![BigInt::from(-1)], DecodeError::WordOutOfRange; "negative")]
+#[test_case(vec![BigInt::from(4) << 63], DecodeError::UnknownOpcodeExtension; "unknown extension")]
+#[test_case(vec![0x400680017fff8005u64.into()], DecodeError::MissingImmediate; "missing immediate")]
+#[test_case(
+    vec![0x401e80017fff8005u64.into()],
+    DecodeError::ConflictingFlags("op1_addr");
+    "conflicting op1_addr"
+)]
+#[test_case(
+    vec![0x20877fff7fff7ffeu64.into(), 5.into()],
+    DecodeError::InvalidFlagCombination;
+    "ret with immediate"
+)]
+#[test_case(
+    vec![0x288b7fff7fff7ffeu64.into()],
+    DecodeError::InvalidFlagCombination;
+    "ret with ap++"
+)]
+#[test_case(
+    vec![(BigInt::from(0x00137fff7ffb7ffdu64) + (BigInt::from(1) << 63))],
+    DecodeError::InvalidFlagCombination;
+    "blake2s without ap++"
+)]
+fn test_decode_errors(words: Vec<BigInt>, error: DecodeError) {
+    assert_eq!(decode(&words), Err(error));
+}
+
+/// Returns the flags of a stone instruction with the given parts, and registers based on fp.
+fn flags(
+    op1_addr: Op1Addr,
+    res: Res,
+    pc_update: PcUpdate,
+    ap_update: ApUpdate,
+    opcode: Opcode,
+) -> InstructionFlags {
+    InstructionFlags {
+        dst_register: Register::FP,
+        op0_register: Register::FP,
+        op1_addr,
+        res,
+        pc_update,
+        ap_update,
+        opcode,
+        opcode_extension: OpcodeExtension::Stone,
+    }
+}
+
+#[test]
+fn test_from_flags() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    assert_eq!(
+        InstructionBody::from_flags(
+            flags(Op1Addr::AP, Res::Add, PcUpdate::Regular, ApUpdate::Add1, Opcode::AssertEq),
+            Offsets { off0: 1, off1: -3, off2: 2 },
+            None,
+        ),
+        Ok(AssertEqInstruction::add(
+            fp(1),
+            fp(-3),
+            DerefOrImmediate::from(CellRef { register: Register::AP, offset: 2 })
+        ))
+    );
+    assert_eq!(
+        InstructionBody::from_flags(
+            flags(Op1Addr::Imm, Res::Op1, PcUpdate::Regular, ApUpdate::Regular, Opcode::AssertEq),
+            Offsets { off0: 1, off1: -1, off2: 1 },
+            Some(BigInt::from(7)),
+        ),
+        Ok(AssertEqInstruction::immediate(fp(1), 7))
+    );
+    assert_eq!(
+        InstructionBody::from_flags(
+            flags(Op1Addr::Imm, Res::Op1, PcUpdate::JumpRel, ApUpdate::Regular, Opcode::Nop),
+            Offsets { off0: -1, off1: -1, off2: 1 },
+            Some(BigInt::from(-4)),
+        ),
+        Ok(InstructionBody::Jump(JumpInstruction {
+            target: DerefOrImmediate::from(-4),
+            relative: true
+        }))
+    );
+    assert_eq!(
+        InstructionBody::from_flags(
+            flags(Op1Addr::FP, Res::Op1, PcUpdate::Jump, ApUpdate::Add1, Opcode::Nop),
+            Offsets { off0: -1, off1: -1, off2: -3 },
+            None,
+        ),
+        Ok(InstructionBody::Jump(JumpInstruction {
+            target: DerefOrImmediate::from(fp(-3)),
+            relative: false
+        }))
+    );
+}
+
+#[test_case(
+    flags(Op1Addr::Imm, Res::Op1, PcUpdate::Regular, ApUpdate::Regular, Opcode::AssertEq),
+    None,
+    DecodeError::MissingImmediate;
+    "missing immediate"
+)]
+#[test_case(
+    flags(Op1Addr::FP, Res::Op1, PcUpdate::Regular, ApUpdate::Regular, Opcode::AssertEq),
+    Some(BigInt::from(7)),
+    DecodeError::InvalidFlagCombination;
+    "unexpected immediate"
+)]
+#[test_case(
+    flags(Op1Addr::FP, Res::Add, PcUpdate::JumpRel, ApUpdate::Regular, Opcode::Nop),
+    None,
+    DecodeError::InvalidFlagCombination;
+    "jump with binary operation"
+)]
+#[test_case(
+    flags(Op1Addr::FP, Res::Op1, PcUpdate::Jump, ApUpdate::Regular, Opcode::AssertEq),
+    None,
+    DecodeError::InvalidFlagCombination;
+    "assert_eq with jump"
+)]
+fn test_from_flags_errors(flags: InstructionFlags, imm: Option<BigInt>, error: DecodeError) {
+    assert_eq!(
+        InstructionBody::from_flags(flags, Offsets { off0: -1, off1: -1, off2: 1 }, imm),
+        Err(error)
+    );
+}
+
+#[cfg(feature = "testing")]
+proptest::proptest! {
+    #[test]
+    fn test_decode_round_trip_arbitrary(body: InstructionBody, inc_ap: bool) {
+        // Only some instructions allow choosing whether ap is incremented.
+        let inc_ap = (inc_ap && body.allows_inc_ap()) || body.requires_inc_ap();
+        let instruction = Instruction::new(body, inc_ap);
+        match instruction.encode() {
+            Ok(encoded) => proptest::prop_assert_eq!(
+                decode_instruction(&encoded),
+                Ok((instruction, encoded.len()))
+            ),
+            // The keccak instruction has no opcode extension yet.
+            Err(error) => {
+                proptest::prop_assert!(matches!(instruction.body, InstructionBody::Keccak(_)));
+                proptest::prop_assert_eq!(error, crate::assembler::AssembleError::NoOpcodeExtension);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_decode_mod_round_trip() {
+    let stark_prime = (BigInt::from(1) << 251) + (BigInt::from(17) << 192) + 1;
+    let mersenne_prime = (BigInt::from(1) << 127) - 1;
+    let m31_prime = BigInt::from((1u64 << 31) - 1);
+    for prime in [stark_prime, mersenne_prime, m31_prime] {
+        for instruction in casm! {
+            jmp rel -5, ap++;
+            [fp + -5] = [ap + 1] * 7;
+            call rel 3;
+        }
+        .instructions
+        {
+            let words = instruction.encode_mod(&prime).unwrap();
+            assert_eq!(
+                decode_instruction_mod(&words, &prime),
+                Ok((instruction.clone(), words.len()))
+            );
+        }
+    }
+}
@@ -2,10 +2,13 @@
 use alloc::{vec, vec::Vec};
 
 use num_bigint::BigInt;
+use num_traits::Euclid;
 
 use crate::assembler::{
-    ApUpdate, FpUpdate, InstructionRepr, Op1Addr, Opcode, OpcodeExtension, PcUpdate, Res,
+    ApUpdate, AssembleError, FpUpdate, InstructionRepr, Op1Addr, Opcode, OpcodeExtension, PcUpdate,
+    Res,
 };
+use crate::instructions::Instruction;
 use crate::operand::Register;
 
 #[cfg(test)]
@@ -132,3 +135,42 @@ impl InstructionRepr {
         }
     }
 }
+
+impl Instruction {
+    /// Returns the field elements encoding the instruction, as expected by the Cairo VM.
+    /// The result has exactly `self.body.op_size()` words.
+    ///
+    /// Fails for instructions that cannot be assembled, see [Instruction::assemble].
+    pub fn encode(&self) -> Result<Vec<BigInt>, AssembleError> {
+        Ok(self.assemble()?.encode())
+    }
+
+    /// Returns the words encoding the instruction for the field of the given prime, with the
+    /// immediate, if any, reduced into the range `[0, prime)`.
+    ///
+    /// The instruction word is a 63-bit integer of flags and offsets, which does not depend on the
+    /// prime, and is left as is. This lets small primes, such as the M31 prime 2^31 - 1, be used,
+    /// although the instruction word does not fit in their fields.
+    pub fn encode_mod(&self, prime: &BigInt) -> Result<Vec<BigInt>, AssembleError> {
+        let mut words = self.encode()?;
+        for word in &mut words[1..] {
+            *word = word.rem_euclid(prime);
+        }
+        Ok(words)
+    }
+}
+
+/// Encodes the instructions one by one into `sink`, without collecting the words of the whole
+/// program first.
+///
+/// Fails on the first instruction that cannot be assembled, after the words of the instructions
+/// preceding it were written.
+pub fn assemble_into(
+    instructions: &[Instruction],
+    sink: &mut impl Extend<BigInt>,
+) -> Result<(), AssembleError> {
+    for instruction in instructions {
+        sink.extend(instruction.encode()?);
+    }
+    Ok(())
+}
@@ -5,8 +5,14 @@ use num_bigint::BigInt;
 use pretty_assertions::assert_eq;
 use test_case::test_case;
 
+use super::assemble_into;
+use crate::assembler::AssembleError;
 use crate::casm;
 use crate::inline::CasmContext;
+use crate::instructions::{
+    Blake2sCompressInstruction, Instruction, InstructionBody, KeccakInstruction, RetInstruction,
+};
+use crate::operand::{CellRef, Register};
 
 #[test_case(
     casm!(jmp abs 3;),
@@ -76,7 +82,7 @@ use crate::inline::CasmContext;
 fn test_encode(mut casm: CasmContext, encoding: u64, immediate: Option<i16>) {
     let enc = BigInt::from(encoding);
     assert_eq!(
-        casm.instructions.remove(0).assemble().encode(),
+        casm.instructions.remove(0).encode().unwrap(),
         if let Some(imm) = immediate { vec![enc, BigInt::from(imm)] } else { vec![enc] }
     );
 }
@@ -108,6 +114,117 @@ fn test_encode(mut casm: CasmContext, encoding: u64, immediate: Option<i16>) {
 fn test_encode_multiple(casm: CasmContext, expected: Vec<i128>) {
     let exp: Vec<BigInt> = expected.into_iter().map(BigInt::from).collect();
     let enc: Vec<BigInt> =
-        casm.instructions.iter().flat_map(|inst| inst.assemble().encode()).collect();
+        casm.instructions.iter().flat_map(|inst| inst.assemble().unwrap().encode()).collect();
     assert_eq!(enc, exp);
 }
+
+#[test_case(
+    casm!([ap + 0] = [[fp + -3] + 2], ap++;),
+    vec![0x480280027ffd8000];
+    "[ap + 0] = [[fp + -3] + 2], ap++;"
+)]
+#[test_case(
+    casm!([fp + 1] = [ap + -1] * 3;),
+    vec![0x404580017fff8001, 3];
+    "[fp + 1] = [ap + -1] * 3;"
+)]
+#[test_case(
+    casm!(call abs [fp + -1];),
+    vec![0x10887fff80018000];
+    "call abs [fp + -1];"
+)]
+#[test_case(
+    casm!(jmp abs [ap + 2], ap++;),
+    vec![0x89380027fff7fff];
+    "jmp abs [ap + 2], ap++;"
+)]
+fn test_instruction_encode(mut casm: CasmContext, expected: Vec<u64>) {
+    let instruction = casm.instructions.remove(0);
+    let encoded = instruction.encode().unwrap();
+    assert_eq!(encoded.len(), instruction.body.op_size());
+    assert_eq!(encoded, expected.into_iter().map(BigInt::from).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_blake2s_encode() {
+    let blake2s = |finalize| {
+        Instruction::new(
+            InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+                state: CellRef { register: Register::FP, offset: -5 },
+                byte_count: CellRef { register: Register::FP, offset: -3 },
+                message: CellRef { register: Register::AP, offset: -1 },
+                finalize,
+            }),
+            true,
+        )
+    };
+    let flags_and_offsets = BigInt::from(0x08137fff7ffb7ffdu64);
+    assert_eq!(blake2s(false).encode(), Ok(vec![&flags_and_offsets + (BigInt::from(1) << 63)]));
+    assert_eq!(blake2s(true).encode(), Ok(vec![&flags_and_offsets + (BigInt::from(2) << 63)]));
+}
+
+#[test]
+fn test_keccak_encode() {
+    let keccak = Instruction::new(
+        InstructionBody::Keccak(KeccakInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -3 },
+            message: CellRef { register: Register::AP, offset: -1 },
+            finalize: false,
+        }),
+        true,
+    );
+    assert_eq!(keccak.assemble(), Err(AssembleError::NoOpcodeExtension));
+    assert_eq!(keccak.encode(), Err(AssembleError::NoOpcodeExtension));
+    let instructions = [casm!(ret;).instructions.remove(0), keccak];
+    let mut words = vec![];
+    assert_eq!(assemble_into(&instructions, &mut words), Err(AssembleError::NoOpcodeExtension));
+    // The words preceding the failing instruction are written.
+    assert_eq!(words, instructions[0].encode().unwrap());
+}
+
+#[test]
+fn test_invalid_inc_ap_encode() {
+    let ret = Instruction::new(InstructionBody::Ret(RetInstruction {}), true);
+    assert_eq!(ret.encode(), Err(AssembleError::InvalidIncAp));
+    let stark_prime = (BigInt::from(1) << 251) + (BigInt::from(17) << 192) + 1;
+    assert_eq!(ret.encode_mod(&stark_prime), Err(AssembleError::InvalidIncAp));
+    let mut words = vec![];
+    assert_eq!(assemble_into(&[ret], &mut words), Err(AssembleError::InvalidIncAp));
+    assert!(words.is_empty());
+}
+
+#[test]
+fn test_encode_mod() {
+    let stark_prime = (BigInt::from(1) << 251) + (BigInt::from(17) << 192) + 1;
+    let mersenne_prime = (BigInt::from(1) << 127) - 1;
+    let instruction = casm!(jmp rel -5, ap++;).instructions.remove(0);
+    let word = BigInt::from(0x90780017fff7fffu64);
+    assert_eq!(instruction.encode_mod(&stark_prime), Ok(vec![word.clone(), &stark_prime - 5]));
+    assert_eq!(
+        instruction.encode_mod(&mersenne_prime),
+        Ok(vec![word.clone(), &mersenne_prime - 5])
+    );
+    // The instruction word does not depend on the prime, even when it does not fit in the field.
+    let m31_prime = BigInt::from((1u64 << 31) - 1);
+    assert_eq!(instruction.encode_mod(&m31_prime), Ok(vec![word, &m31_prime - 5]));
+    let instruction = casm!([ap + 0] = [fp + -3] + (1_i64 << 40);).instructions.remove(0);
+    assert_eq!(instruction.encode_mod(&m31_prime).unwrap()[1], BigInt::from(1 << 9));
+}
+
+#[test]
+fn test_assemble_into() {
+    let instructions = casm! {
+        [fp + -5] = 1, ap++;
+        [ap + 0] = [fp + -5] * [ap + -1], ap++;
+        jmp rel (-5) if [ap + -1] != 0;
+        ret;
+    }
+    .instructions;
+    let batch: Vec<BigInt> =
+        instructions.iter().flat_map(|instruction| instruction.encode().unwrap()).collect();
+    let mut words = vec![BigInt::from(7)];
+    assert_eq!(assemble_into(&instructions, &mut words), Ok(()));
+    assert_eq!(words[0], BigInt::from(7));
+    assert_eq!(words[1..], batch);
+}
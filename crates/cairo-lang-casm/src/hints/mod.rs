@@ -3,6 +3,8 @@
 use alloc::{
     format,
     string::{String, ToString},
+    vec,
+    vec::Vec,
 };
 use core::fmt::{Display, Formatter};
 
@@ -17,7 +19,7 @@ mod test;
 // Represents a cairo hint.
 // Note: Hint encoding should be backwards-compatible. This is an API guarantee.
 // For example, new variants should have new `index`.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize), serde(untagged))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -38,6 +40,74 @@ impl Hint {
     pub fn representing_string(&self) -> String {
         format!("{:?}", self)
     }
+
+    /// Returns the cells the hint reads and writes, if the hint only computes its outputs from its
+    /// inputs.
+    ///
+    /// Returns None for hints that may have other effects, such as allocating segments or updating
+    /// the state of the dict manager, and for hints reading memory through a pointer, as the cells
+    /// such hints access are not known statically.
+    pub fn accesses(&self) -> Option<HintAccesses> {
+        let Hint::Core(CoreHintBase::Core(hint)) = self else {
+            return None;
+        };
+        let (inputs, writes) = match hint {
+            CoreHint::TestLessThan { lhs, rhs, dst }
+            | CoreHint::TestLessThanOrEqual { lhs, rhs, dst }
+            | CoreHint::TestLessThanOrEqualAddress { lhs, rhs, dst } => {
+                (vec![lhs, rhs], vec![*dst])
+            }
+            CoreHint::WideMul128 { lhs, rhs, high, low } => (vec![lhs, rhs], vec![*high, *low]),
+            CoreHint::DivMod { lhs, rhs, quotient, remainder } => {
+                (vec![lhs, rhs], vec![*quotient, *remainder])
+            }
+            CoreHint::SquareRoot { value, dst } => (vec![value], vec![*dst]),
+            CoreHint::LinearSplit { value, scalar, max_x, x, y } => {
+                (vec![value, scalar, max_x], vec![*x, *y])
+            }
+            CoreHint::FieldSqrt { val, sqrt } => (vec![val], vec![*sqrt]),
+            _ => return None,
+        };
+        let mut reads = vec![];
+        for input in inputs {
+            match input {
+                ResOperand::Deref(cell) => reads.push(*cell),
+                ResOperand::DoubleDeref(_, _) => return None,
+                ResOperand::Immediate(_) => {}
+                ResOperand::BinOp(bin_op) => {
+                    reads.push(bin_op.a);
+                    if let DerefOrImmediate::Deref(cell) = &bin_op.b {
+                        reads.push(*cell);
+                    }
+                }
+            }
+        }
+        Some(HintAccesses { reads, writes })
+    }
+
+    /// Returns true if the two hints may run in either order with the same effect, that is, if
+    /// the cells they access are known and neither writes a cell the other accesses.
+    pub fn is_independent_of(&self, other: &Hint) -> bool {
+        let (Some(this), Some(other)) = (self.accesses(), other.accesses()) else {
+            return false;
+        };
+        let conflicts =
+            |a: &HintAccesses, b: &HintAccesses| a.writes.iter().any(|cell| b.accesses(cell));
+        !conflicts(&this, &other) && !conflicts(&other, &this)
+    }
+}
+
+/// The cells accessed by a hint, as returned by [Hint::accesses].
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct HintAccesses {
+    pub reads: Vec<CellRef>,
+    pub writes: Vec<CellRef>,
+}
+impl HintAccesses {
+    /// Returns true if the given cell is read or written.
+    pub fn accesses(&self, cell: &CellRef) -> bool {
+        self.reads.contains(cell) || self.writes.contains(cell)
+    }
 }
 
 impl From<CoreHint> for Hint {
@@ -73,7 +143,7 @@ impl PythonicHint for Hint {
 }
 
 /// Represents a hint that triggers a system call.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -95,7 +165,7 @@ pub enum StarknetHint {
 }
 
 // Represents a cairo core hint.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize), serde(untagged))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -120,7 +190,7 @@ impl From<DeprecatedHint> for CoreHintBase {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -306,7 +376,7 @@ pub enum CoreHint {
 
 /// Represents a deprecated hint which is kept for backward compatibility of previously deployed
 /// contracts.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -342,7 +412,7 @@ pub enum DeprecatedHint {
 /// Represents an external hint.
 ///
 /// Hints used out of the Sierra environment, mostly for creating external wrapper for code.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(
     feature = "parity-scale-codec",
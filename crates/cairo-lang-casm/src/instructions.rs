@@ -1,57 +1,583 @@
 #[cfg(not(feature = "std"))]
-use alloc::{vec, vec::Vec};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 use core::fmt::Display;
 
+use cairo_lang_utils::bigint::BigIntAsHex;
+use num_bigint::BigInt;
+use num_traits::{One, ToPrimitive, Zero};
+
 use crate::hints::{Hint, PythonicHint};
-use crate::operand::{CellRef, DerefOrImmediate, ResOperand};
+use crate::operand::{CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+use crate::visitor::OperandVisitorMut;
 
 #[cfg(test)]
 #[path = "instructions_test.rs"]
 mod test;
 
 // An enum of Cairo instructions.
-#[derive(Debug, Eq, PartialEq, Clone)]
+//
+// Binary serialization formats (such as bincode) identify the variants by their index, so the
+// order of the variants is part of the serialized form: new variants must only be added at the
+// end.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum InstructionBody {
     AddAp(AddApInstruction),
     AssertEq(AssertEqInstruction),
-    QM31AssertEq(AssertEqInstruction),
+    QM31AssertEq(QM31AssertEqInstruction),
     Call(CallInstruction),
     Jnz(JnzInstruction),
     Jump(JumpInstruction),
     Ret(RetInstruction),
     Blake2sCompress(Blake2sCompressInstruction),
+    Keccak(KeccakInstruction),
 }
 impl InstructionBody {
+    /// Returns the number of words the instruction occupies in the encoded program.
+    ///
+    /// An instruction is encoded as a single word describing its flags and offsets, followed by
+    /// an additional word iff it has an immediate operand.
+    /// This holds for QM31 instructions as well, as a QM31 immediate is packed into a single word.
     pub fn op_size(&self) -> usize {
-        // TODO(spapini): Make this correct.
         match self {
             InstructionBody::AddAp(insn) => insn.op_size(),
-            InstructionBody::AssertEq(insn) | InstructionBody::QM31AssertEq(insn) => insn.op_size(),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => insn.op_size(),
             InstructionBody::Call(insn) => insn.op_size(),
             InstructionBody::Jump(insn) => insn.op_size(),
             InstructionBody::Jnz(insn) => insn.op_size(),
             InstructionBody::Ret(insn) => insn.op_size(),
             InstructionBody::Blake2sCompress(insn) => insn.op_size(),
+            InstructionBody::Keccak(insn) => insn.op_size(),
+        }
+    }
+
+    /// Returns the canonical no-op instruction, `[ap + 0] = [ap + 0]`, to be used for padding.
+    ///
+    /// The no-op takes a single word. Note that it reads `[ap + 0]`, so it may only be placed
+    /// where that cell is already known, as the VM cannot deduce it otherwise.
+    pub fn nop() -> InstructionBody {
+        let cell = CellRef { register: Register::AP, offset: 0 };
+        InstructionBody::AssertEq(AssertEqInstruction { a: cell, b: ResOperand::Deref(cell) })
+    }
+
+    /// Returns true if the instruction is the no-op returned by [Self::nop].
+    pub fn is_nop(&self) -> bool {
+        *self == Self::nop()
+    }
+
+    /// Returns true if executing the instruction may cause a VM error.
+    ///
+    /// Cells read directly by the instruction are assumed to be initialized, so only the
+    /// following may fail:
+    /// - `assert_eq`, in both fields, as the assertion may not hold.
+    /// - Any operand `[[x] + k]`, as the computed address may not be a valid initialized cell.
+    /// - The blake2s and keccak opcodes, which read their operands through the addresses held in
+    ///   their cells, and assert their output.
+    ///
+    /// Branches, `ret` and `ap += x` without a double deref never fail: the branch targets are
+    /// only fetched on the next step.
+    pub fn may_fail(&self) -> bool {
+        match self {
+            InstructionBody::AssertEq(_)
+            | InstructionBody::QM31AssertEq(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => true,
+            InstructionBody::AddAp(insn) => matches!(insn.operand, ResOperand::DoubleDeref(..)),
+            InstructionBody::Call(_)
+            | InstructionBody::Jump(_)
+            | InstructionBody::Jnz(_)
+            | InstructionBody::Ret(_) => false,
+        }
+    }
+
+    /// Returns true if the instruction may be combined with `ap++`.
+    ///
+    /// `ap += x` already sets the ap update, `call` pushes a frame and `ret` restores the frame of
+    /// the caller, so none of them has room for another ap update.
+    pub fn allows_inc_ap(&self) -> bool {
+        match self {
+            InstructionBody::AddAp(_) | InstructionBody::Call(_) | InstructionBody::Ret(_) => false,
+            InstructionBody::AssertEq(_)
+            | InstructionBody::QM31AssertEq(_)
+            | InstructionBody::Jump(_)
+            | InstructionBody::Jnz(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => true,
+        }
+    }
+
+    /// Returns true if the instruction must be combined with `ap++`.
+    ///
+    /// The blake2s and keccak opcodes always advance ap past their output, so their encoding has
+    /// no form without the ap update.
+    pub fn requires_inc_ap(&self) -> bool {
+        matches!(self, InstructionBody::Blake2sCompress(_) | InstructionBody::Keccak(_))
+    }
+
+    /// Returns true if the instruction has an immediate operand, which is encoded in a word
+    /// following the instruction word.
+    pub fn has_immediate(&self) -> bool {
+        match self {
+            InstructionBody::AddAp(insn) => res_operand_has_immediate(&insn.operand),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => {
+                res_operand_has_immediate(&insn.b)
+            }
+            InstructionBody::Call(insn) => insn.target.is_immediate(),
+            InstructionBody::Jump(insn) => insn.target.is_immediate(),
+            InstructionBody::Jnz(insn) => insn.jump_offset.is_immediate(),
+            InstructionBody::Ret(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => false,
+        }
+    }
+
+    /// Returns the immediate operand of the instruction, which is encoded in the word following
+    /// the instruction word, if it has one.
+    pub fn immediate(&self) -> Option<&BigInt> {
+        match self {
+            InstructionBody::AddAp(insn) => res_operand_immediate(&insn.operand),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => {
+                res_operand_immediate(&insn.b)
+            }
+            InstructionBody::Call(insn) => insn.target.as_immediate(),
+            InstructionBody::Jump(insn) => insn.target.as_immediate(),
+            InstructionBody::Jnz(insn) => insn.jump_offset.as_immediate(),
+            InstructionBody::Ret(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => None,
+        }
+    }
+
+    /// Returns a mutable reference to the immediate operand of the instruction, as returned by
+    /// [Self::immediate], allowing it to be patched in place.
+    pub fn immediate_mut(&mut self) -> Option<&mut BigInt> {
+        match self {
+            InstructionBody::AddAp(insn) => res_operand_immediate_mut(&mut insn.operand),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => {
+                res_operand_immediate_mut(&mut insn.b)
+            }
+            InstructionBody::Call(insn) => deref_or_immediate_immediate_mut(&mut insn.target),
+            InstructionBody::Jump(insn) => deref_or_immediate_immediate_mut(&mut insn.target),
+            InstructionBody::Jnz(insn) => deref_or_immediate_immediate_mut(&mut insn.jump_offset),
+            InstructionBody::Ret(_)
+            | InstructionBody::Blake2sCompress(_)
+            | InstructionBody::Keccak(_) => None,
+        }
+    }
+
+    /// Returns the target of the instruction, if it is a relative branch (`jmp rel`, `call rel` or
+    /// `jnz`).
+    pub fn relative_target(&self) -> Option<&DerefOrImmediate> {
+        match self {
+            InstructionBody::Call(insn) if insn.relative => Some(&insn.target),
+            InstructionBody::Jump(insn) if insn.relative => Some(&insn.target),
+            InstructionBody::Jnz(insn) => Some(&insn.jump_offset),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the target of the instruction, as returned by
+    /// [Self::relative_target], allowing it to be patched in place.
+    pub fn relative_target_mut(&mut self) -> Option<&mut DerefOrImmediate> {
+        match self {
+            InstructionBody::Call(insn) if insn.relative => Some(&mut insn.target),
+            InstructionBody::Jump(insn) if insn.relative => Some(&mut insn.target),
+            InstructionBody::Jnz(insn) => Some(&mut insn.jump_offset),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the instruction operates over the QM31 extension field rather than the
+    /// base field.
+    ///
+    /// Only assert_eq instructions have a QM31 mode, as the QM31 opcode extension is not supported
+    /// for other opcodes. The binary operation of such an instruction is computed over QM31.
+    /// [crate::operand::BinOpOperand] carries no QM31 flag of its own, as the mode is an opcode
+    /// extension of the whole instruction, and a flag on the operand could contradict it.
+    pub fn is_qm31(&self) -> bool {
+        matches!(self, InstructionBody::QM31AssertEq(_))
+    }
+
+    /// Returns the cells the instruction reads from memory.
+    ///
+    /// For a double deref operand, only the cell holding the address is returned, as the address
+    /// of the second cell is not known statically.
+    pub fn reads(&self) -> Vec<CellRef> {
+        match self {
+            InstructionBody::AddAp(insn) => insn.operand.deref_cells(),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => insn.b.deref_cells(),
+            InstructionBody::Call(insn) => deref_or_immediate_cells(&insn.target),
+            InstructionBody::Jump(insn) => deref_or_immediate_cells(&insn.target),
+            InstructionBody::Jnz(insn) => {
+                let mut cells = deref_or_immediate_cells(&insn.jump_offset);
+                cells.push(insn.condition);
+                cells
+            }
+            // The previous fp and the return address.
+            InstructionBody::Ret(_) => vec![
+                CellRef { register: Register::FP, offset: -2 },
+                CellRef { register: Register::FP, offset: -1 },
+            ],
+            InstructionBody::Blake2sCompress(insn) => insn.cell_refs().to_vec(),
+            InstructionBody::Keccak(insn) => vec![insn.state, insn.byte_count, insn.message],
+        }
+    }
+
+    /// Returns the cells the instruction writes to memory, relative to the registers before the
+    /// instruction is executed.
+    pub fn writes(&self) -> Vec<CellRef> {
+        match self {
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => vec![insn.a],
+            // The current fp and the return address.
+            InstructionBody::Call(_) => vec![
+                CellRef { register: Register::AP, offset: 0 },
+                CellRef { register: Register::AP, offset: 1 },
+            ],
+            InstructionBody::Blake2sCompress(_) | InstructionBody::Keccak(_) => {
+                vec![CellRef { register: Register::AP, offset: 0 }]
+            }
+            InstructionBody::AddAp(_)
+            | InstructionBody::Jump(_)
+            | InstructionBody::Jnz(_)
+            | InstructionBody::Ret(_) => vec![],
         }
     }
 }
+
+/// Shifts the fp based cells it visits by `delta`, recording whether any shift overflowed.
+struct FpRebaser {
+    delta: i16,
+    overflowed: bool,
+}
+impl OperandVisitorMut for FpRebaser {
+    fn visit_cell_ref(&mut self, cell: &mut CellRef) {
+        if cell.register != Register::FP {
+            return;
+        }
+        match cell.offset_by(self.delta) {
+            Some(rebased) => *cell = rebased,
+            None => self.overflowed = true,
+        }
+    }
+}
+
+/// Returns the JSON schema of the serialized form of [InstructionBody].
+#[cfg(feature = "schemars")]
+pub fn instruction_body_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(InstructionBody)
+}
+
+/// A violation of the invariants of an instruction.
+#[derive(Debug, Eq, PartialEq)]
+pub enum InstructionError {
+    /// A relative jump or call with a zero offset, which branches to itself forever. Holds the name
+    /// of the offset field. A jnz with a zero offset is reported as [JnzError::SelfBranch].
+    SelfBranch(&'static str),
+    /// A QM31 assert_eq whose operand is not a binary operation, which the QM31 opcode extension
+    /// does not support.
+    QM31NonBinOp(ResOperand),
+    /// A jnz violating one of the invariants checked by [JnzInstruction::validate].
+    Jnz(JnzError),
+    /// An `ap++` on an instruction that does not allow it, as given by
+    /// [InstructionBody::allows_inc_ap].
+    IncApNotAllowed,
+    /// A missing `ap++` on an instruction that requires it, as given by
+    /// [InstructionBody::requires_inc_ap].
+    IncApRequired,
+    /// A hint whose pythonic text contains the hint terminator, `%}`, so that the formatted
+    /// instruction does not parse back. Holds the index of the hint.
+    HintTerminator(usize),
+}
+impl Display for InstructionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InstructionError::SelfBranch(field) => {
+                write!(f, "Relative branch with a zero `{field}` branches to itself")
+            }
+            InstructionError::QM31NonBinOp(operand) => {
+                write!(f, "QM31 assert_eq with non binary operation operand `{operand}`")
+            }
+            InstructionError::Jnz(error) => write!(f, "{error}"),
+            InstructionError::IncApNotAllowed => write!(f, "Instruction cannot have an ap++"),
+            InstructionError::IncApRequired => write!(f, "Instruction must have an ap++"),
+            InstructionError::HintTerminator(index) => {
+                write!(f, "Hint #{index} contains the hint terminator `%}}`")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InstructionError {}
+
+/// A violation of the invariants of a jnz instruction.
+#[derive(Debug, Eq, PartialEq)]
+pub enum JnzError {
+    /// A zero jump offset, which jumps to the jnz itself for as long as the condition holds.
+    SelfBranch,
+    /// A jump offset of 1, which jumps into the immediate word of the jnz holding this very offset.
+    IntoImmediate,
+    /// The jump offset is read from the condition cell, so the jump is taken by exactly the
+    /// offset that was tested to be nonzero.
+    ConditionIsOffset,
+}
+impl Display for JnzError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            JnzError::SelfBranch => write!(f, "Jnz with a zero offset jumps to itself"),
+            JnzError::IntoImmediate => write!(f, "Jnz jumps into its own immediate"),
+            JnzError::ConditionIsOffset => write!(f, "Jnz condition is its own jump offset"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for JnzError {}
+
+/// An offset of a cell reference that does not fit in the offset encoding range.
+#[derive(Debug, Eq, PartialEq)]
+pub struct OffsetOverflow;
+impl Display for OffsetOverflow {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Offset overflow")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OffsetOverflow {}
+
+/// An instruction size, in words, outside of the range the VM can decode.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SizeError {
+    pub size: usize,
+}
+impl Display for SizeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Instruction of {} words, where 1 or 2 are expected", self.size)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SizeError {}
+
+/// The range of legal instruction sizes, in words: an instruction word, optionally followed by an
+/// immediate.
+const LEGAL_OP_SIZES: core::ops::RangeInclusive<usize> = 1..=2;
+
+/// Checks that `size` is a legal instruction size.
+fn check_op_size(size: usize) -> Result<usize, SizeError> {
+    if LEGAL_OP_SIZES.contains(&size) { Ok(size) } else { Err(SizeError { size }) }
+}
+
+impl InstructionBody {
+    /// Same as [Self::op_size], but fails if the size is not one the VM can decode.
+    pub fn op_size_checked(&self) -> Result<usize, SizeError> {
+        check_op_size(self.op_size())
+    }
+
+    /// Checks that the instruction does not violate any invariant.
+    ///
+    /// The check is conservative, and only rejects instructions that are invalid in any context.
+    pub fn validate(&self) -> Result<(), InstructionError> {
+        match self {
+            InstructionBody::Call(insn) if insn.relative && is_zero_immediate(&insn.target) => {
+                Err(InstructionError::SelfBranch("target"))
+            }
+            InstructionBody::Jump(insn) if insn.relative && is_zero_immediate(&insn.target) => {
+                Err(InstructionError::SelfBranch("target"))
+            }
+            InstructionBody::Jnz(insn) => insn.validate().map_err(InstructionError::Jnz),
+            InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn))
+                if !matches!(insn.b, ResOperand::BinOp(_)) =>
+            {
+                Err(InstructionError::QM31NonBinOp(insn.b.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Shifts all the fp based cell references of the instruction by `delta`, leaving the ap based
+    /// ones untouched. This is the change required when the frame of a function is merged into
+    /// that of its caller.
+    ///
+    /// On overflow, the instruction is left unchanged. Note that the hints of an instruction
+    /// should be rebased along with it, as done by [Instruction::rebase_fp].
+    pub fn rebase_fp(&mut self, delta: i16) -> Result<(), OffsetOverflow> {
+        let mut rebased = self.clone();
+        let mut rebaser = FpRebaser { delta, overflowed: false };
+        rebased.walk_operands_mut(&mut rebaser);
+        if rebaser.overflowed {
+            return Err(OffsetOverflow);
+        }
+        *self = rebased;
+        Ok(())
+    }
+
+    /// Returns a copy of the instruction with all the occurrences of the cell reference `from`
+    /// replaced by `to`.
+    pub fn substitute_cell(&self, from: CellRef, to: CellRef) -> InstructionBody {
+        struct CellSubstituter {
+            from: CellRef,
+            to: CellRef,
+        }
+        impl OperandVisitorMut for CellSubstituter {
+            fn visit_cell_ref(&mut self, cell: &mut CellRef) {
+                if *cell == self.from {
+                    *cell = self.to;
+                }
+            }
+        }
+
+        let mut substituted = self.clone();
+        substituted.walk_operands_mut(&mut CellSubstituter { from, to });
+        substituted
+    }
+}
+
+fn res_operand_has_immediate(operand: &ResOperand) -> bool {
+    match operand {
+        ResOperand::Deref(_) | ResOperand::DoubleDeref(_, _) => false,
+        ResOperand::Immediate(_) => true,
+        ResOperand::BinOp(bin_op) => bin_op.b.is_immediate(),
+    }
+}
+
+/// Returns the offset from `self_offset` to the immediate absolute target `target`, if it is the
+/// start of one of the instructions at the offsets `program`.
+fn absolute_to_relative(
+    target: &DerefOrImmediate,
+    self_offset: usize,
+    program: &[usize],
+) -> Option<DerefOrImmediate> {
+    let target = target.as_immediate()?;
+    let is_instruction_start =
+        usize::try_from(target).is_ok_and(|target| program.binary_search(&target).is_ok());
+    is_instruction_start.then(|| DerefOrImmediate::from(target - self_offset))
+}
+
+/// Returns the immediate of the given operand, if it has one.
+fn res_operand_immediate(operand: &ResOperand) -> Option<&BigInt> {
+    match operand {
+        ResOperand::Deref(_) | ResOperand::DoubleDeref(_, _) => None,
+        ResOperand::Immediate(imm) => Some(&imm.value),
+        ResOperand::BinOp(bin_op) => bin_op.b.as_immediate(),
+    }
+}
+
+/// Returns a mutable reference to the immediate of the given operand, if it has one.
+fn res_operand_immediate_mut(operand: &mut ResOperand) -> Option<&mut BigInt> {
+    match operand {
+        ResOperand::Deref(_) | ResOperand::DoubleDeref(_, _) => None,
+        ResOperand::Immediate(imm) => Some(&mut imm.value),
+        ResOperand::BinOp(bin_op) => deref_or_immediate_immediate_mut(&mut bin_op.b),
+    }
+}
+
+/// Returns a mutable reference to the immediate of the given operand, if it is an immediate.
+fn deref_or_immediate_immediate_mut(operand: &mut DerefOrImmediate) -> Option<&mut BigInt> {
+    match operand {
+        DerefOrImmediate::Deref(_) => None,
+        DerefOrImmediate::Immediate(imm) => Some(&mut imm.value),
+    }
+}
+
+/// Returns true if the given operand is the immediate zero.
+fn is_zero_immediate(operand: &DerefOrImmediate) -> bool {
+    operand.as_immediate().is_some_and(|imm| imm.is_zero())
+}
+
+/// Returns the cells read by the given operand.
+fn deref_or_immediate_cells(operand: &DerefOrImmediate) -> Vec<CellRef> {
+    match operand {
+        DerefOrImmediate::Deref(cell) => vec![*cell],
+        DerefOrImmediate::Immediate(_) => vec![],
+    }
+}
 impl Display for InstructionBody {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             InstructionBody::AddAp(insn) => write!(f, "{insn}",),
             InstructionBody::AssertEq(insn) => write!(f, "{insn}",),
-            InstructionBody::QM31AssertEq(insn) => write!(f, "{{QM31}} {insn}",),
+            InstructionBody::QM31AssertEq(insn) => write!(f, "{insn}",),
             InstructionBody::Call(insn) => write!(f, "{insn}",),
             InstructionBody::Jnz(insn) => write!(f, "{insn}",),
             InstructionBody::Jump(insn) => write!(f, "{insn}",),
             InstructionBody::Ret(insn) => write!(f, "{insn}",),
             InstructionBody::Blake2sCompress(insn) => write!(f, "{insn}",),
+            InstructionBody::Keccak(insn) => write!(f, "{insn}",),
         }
     }
 }
 
+impl From<AddApInstruction> for InstructionBody {
+    fn from(insn: AddApInstruction) -> Self {
+        InstructionBody::AddAp(insn)
+    }
+}
+/// Wraps an assert_eq as [InstructionBody::AssertEq], over the base field. QM31 asserts are
+/// converted from [QM31AssertEqInstruction].
+impl From<AssertEqInstruction> for InstructionBody {
+    fn from(insn: AssertEqInstruction) -> Self {
+        InstructionBody::AssertEq(insn)
+    }
+}
+impl From<QM31AssertEqInstruction> for InstructionBody {
+    fn from(insn: QM31AssertEqInstruction) -> Self {
+        InstructionBody::QM31AssertEq(insn)
+    }
+}
+impl From<CallInstruction> for InstructionBody {
+    fn from(insn: CallInstruction) -> Self {
+        InstructionBody::Call(insn)
+    }
+}
+impl From<JnzInstruction> for InstructionBody {
+    fn from(insn: JnzInstruction) -> Self {
+        InstructionBody::Jnz(insn)
+    }
+}
+impl From<JumpInstruction> for InstructionBody {
+    fn from(insn: JumpInstruction) -> Self {
+        InstructionBody::Jump(insn)
+    }
+}
+impl From<RetInstruction> for InstructionBody {
+    fn from(insn: RetInstruction) -> Self {
+        InstructionBody::Ret(insn)
+    }
+}
+impl From<Blake2sCompressInstruction> for InstructionBody {
+    fn from(insn: Blake2sCompressInstruction) -> Self {
+        InstructionBody::Blake2sCompress(insn)
+    }
+}
+impl From<KeccakInstruction> for InstructionBody {
+    fn from(insn: KeccakInstruction) -> Self {
+        InstructionBody::Keccak(insn)
+    }
+}
+
 /// Represents an instruction, including the ap++ flag (inc_ap).
-#[derive(Debug, Eq, PartialEq, Clone)]
+///
+/// Note that hints are serialized untagged, so instructions with hints can only be deserialized
+/// from self-describing formats (such as JSON). Deserializing them from a binary format (such as
+/// bincode) fails with an error, so binary caches of programs should hold their hints separately,
+/// or have them removed with [crate::program::strip_hints].
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Instruction {
     pub body: InstructionBody,
     pub inc_ap: bool,
@@ -61,14 +587,177 @@ impl Instruction {
     pub fn new(body: InstructionBody, inc_ap: bool) -> Self {
         Self { body, inc_ap, hints: vec![] }
     }
+
+    pub fn with_hints(body: InstructionBody, inc_ap: bool, hints: Vec<Hint>) -> Self {
+        Self { body, inc_ap, hints }
+    }
+
+    /// Returns instructions without hints for each of the given bodies, all with the same `inc_ap`.
+    pub fn from_bodies(
+        bodies: impl IntoIterator<Item = InstructionBody>,
+        inc_ap: bool,
+    ) -> Vec<Instruction> {
+        bodies.into_iter().map(|body| Self::new(body, inc_ap)).collect()
+    }
+
+    /// Checks that the instruction does not violate any invariant: that `ap++` is only set on a
+    /// body allowing it and always set on a body requiring it, as the assembler expects, that no
+    /// hint text contains `%}`, so that the instruction formats to text that parses back, and that
+    /// the body itself passes [InstructionBody::validate].
+    pub fn validate(&self) -> Result<(), InstructionError> {
+        if self.inc_ap && !self.body.allows_inc_ap() {
+            return Err(InstructionError::IncApNotAllowed);
+        }
+        if !self.inc_ap && self.body.requires_inc_ap() {
+            return Err(InstructionError::IncApRequired);
+        }
+        if let Some(index) =
+            self.hints.iter().position(|hint| has_hint_terminator(&hint.get_pythonic_hint()))
+        {
+            return Err(InstructionError::HintTerminator(index));
+        }
+        self.body.validate()
+    }
+
+    /// Appends a hint to run after the hints already attached to the instruction.
+    pub fn push_hint(&mut self, hint: Hint) -> &mut Self {
+        self.hints.push(hint);
+        self
+    }
+
+    /// Writes the instruction, as formatted by [Display], directly into `w`.
+    ///
+    /// No intermediate strings are allocated for the instruction body, which makes this suitable
+    /// for allocation sensitive environments. Note that rendering hints still allocates, as
+    /// pythonic hints are generated as strings.
+    pub fn write_to(&self, w: &mut impl core::fmt::Write) -> core::fmt::Result {
+        write!(w, "{self}")
+    }
+
+    /// Shifts all the fp based cell references of the instruction and of its hints by `delta`, as
+    /// done for [InstructionBody::rebase_fp].
+    ///
+    /// On overflow, the instruction is left unchanged.
+    pub fn rebase_fp(&mut self, delta: i16) -> Result<(), OffsetOverflow> {
+        let mut rebased = self.clone();
+        let mut rebaser = FpRebaser { delta, overflowed: false };
+        rebased.body.walk_operands_mut(&mut rebaser);
+        for hint in &mut rebased.hints {
+            hint.walk_operands_mut(&mut rebaser);
+        }
+        if rebaser.overflowed {
+            return Err(OffsetOverflow);
+        }
+        *self = rebased;
+        Ok(())
+    }
+
+    /// Returns true if no hint of the instruction reads a cell written by a hint following it.
+    /// Hints run in order, so such a hint would read the cell before it is assigned.
+    ///
+    /// Only the hints whose accesses are known, as given by [Hint::accesses], are checked.
+    pub fn hints_are_ordered(&self) -> bool {
+        let accesses: Vec<_> = self.hints.iter().map(Hint::accesses).collect();
+        accesses.iter().enumerate().all(|(index, hint)| {
+            let Some(hint) = hint else {
+                return true;
+            };
+            accesses[index + 1..]
+                .iter()
+                .flatten()
+                .all(|later| !later.writes.iter().any(|cell| hint.reads.contains(cell)))
+        })
+    }
+
+    /// Sorts the hints of the instruction by their pythonic representation, for a canonical
+    /// order.
+    ///
+    /// A hint is only moved past hints it is independent of, as given by
+    /// [Hint::is_independent_of], so the hints keep their effect. Hence, the result is only fully
+    /// sorted if the hints are independent.
+    pub fn sort_hints(&mut self) {
+        let mut keys: Vec<String> =
+            self.hints.iter().map(|hint| hint.get_pythonic_hint()).collect();
+        for index in 1..self.hints.len() {
+            let mut current = index;
+            while current > 0
+                && keys[current] < keys[current - 1]
+                && self.hints[current].is_independent_of(&self.hints[current - 1])
+            {
+                keys.swap(current, current - 1);
+                self.hints.swap(current, current - 1);
+                current -= 1;
+            }
+        }
+    }
+
+    /// Returns true if the instructions are equal, disregarding their hints.
+    pub fn eq_ignoring_hints(&self, other: &Instruction) -> bool {
+        self.body == other.body && self.inc_ap == other.inc_ap
+    }
+
+    /// Returns the total length, in bytes, of the pythonic texts of the hints of the instruction.
+    ///
+    /// This is the overhead of the hints in serializations holding their pythonic form, on top of
+    /// the words of the instruction itself.
+    pub fn encoded_hint_len(&self) -> usize {
+        self.hints.iter().map(|hint| hint.get_pythonic_hint().len()).sum()
+    }
+
+    /// Returns a terse single-line representation of the instruction for snapshot tests, e.g.
+    /// `([ap + 0] = 5, inc_ap: true, hints: 1)`.
+    ///
+    /// Hints are only counted, so that changes to their text do not affect the snapshots of
+    /// unrelated instructions.
+    pub fn snapshot_repr(&self) -> String {
+        format!("({}, inc_ap: {}, hints: {})", self.body, self.inc_ap, self.hints.len())
+    }
+
+    /// Returns the instruction in assembly syntax on a single line, for logs.
+    ///
+    /// The hints are summarized by the first line of each of their pythonic texts, in a trailing
+    /// comment, e.g. `[ap + 0] = [fp + 1], ap++ // hints: memory[ap + 0] = segments.add()`.
+    pub fn debug_asm(&self) -> String {
+        let mut asm = self.without_hints().to_string();
+        for (index, hint) in self.hints.iter().enumerate() {
+            let pythonic_hint = hint.get_pythonic_hint();
+            let first_line = pythonic_hint.lines().map(str::trim).find(|line| !line.is_empty());
+            asm.push_str(if index == 0 { " // hints: " } else { "; " });
+            asm.push_str(first_line.unwrap_or_default());
+        }
+        asm
+    }
+
+    /// Returns a copy of the instruction without any hints.
+    pub fn without_hints(&self) -> Instruction {
+        Self::new(self.body.clone(), self.inc_ap)
+    }
+
+    /// Removes hints that are equal to the hint right before them.
+    ///
+    /// Hint order is meaningful, so non-adjacent duplicates are kept, as a hint between them may
+    /// have changed the state the later duplicate observes.
+    pub fn dedup_hints(&mut self) {
+        self.hints.dedup();
+    }
 }
 
+/// Formats the instruction with its hints on separate lines before it.
+/// The alternate form (`{:#}`) puts the hints on the same line as the instruction instead, e.g.
+/// `%{ memory[ap + 0] = segments.add() %} [ap + 0] = [fp + 1]`. Note that hints spanning multiple
+/// lines still do so.
+///
+/// A hint is closed by the first `%}` following its opening, and the pythonic text of a hint has
+/// no escape for it, so the output only parses back if no hint text contains `%}`. This is checked
+/// by [Instruction::validate] rather than here, and none of the current hints contains it.
 impl Display for Instruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         for hint in &self.hints {
             let hint_str = hint.get_pythonic_hint();
-            // Skip leading and trailing space if hint starts with `\n`.
-            if hint_str.starts_with('\n') {
+            if f.alternate() {
+                write!(f, "%{{ {} %}} ", hint_str.trim())
+            } else if hint_str.starts_with('\n') {
+                // Skip leading and trailing space if hint starts with `\n`.
                 writeln!(f, "%{{{hint_str}%}}")
             } else {
                 writeln!(f, "%{{ {hint_str} %}}")
@@ -83,8 +772,105 @@ impl Display for Instruction {
     }
 }
 
+/// Returns true if the pythonic text of a hint contains the hint terminator, `%}`.
+fn has_hint_terminator(hint_str: &str) -> bool {
+    hint_str.contains("%}")
+}
+
+/// Displays an instruction prefixed by the word offset it starts at, e.g.
+/// `0x0005: [ap + 0] = [fp + 1], ap++`.
+pub struct OffsetDisplay<'a>(pub &'a Instruction, pub usize);
+impl OffsetDisplay<'_> {
+    /// Returns the offset of the instruction following the displayed one.
+    pub fn next_offset(&self) -> usize {
+        self.1 + self.0.body.op_size()
+    }
+}
+impl Display for OffsetDisplay<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#06x}: {}", self.1, self.0)
+    }
+}
+
+/// A span in the source code, given as a range of byte offsets.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// An instruction, along with the location in the source code it was generated from.
+///
+/// Equality compares the spans as well; compare [Self::instruction] to ignore them.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct InstructionWithLocation {
+    pub instruction: Instruction,
+    pub span: Option<SourceSpan>,
+}
+impl InstructionWithLocation {
+    pub fn instruction(&self) -> &Instruction {
+        &self.instruction
+    }
+
+    pub fn span(&self) -> Option<SourceSpan> {
+        self.span
+    }
+}
+impl Instruction {
+    /// Attaches a source span to the instruction.
+    pub fn with_span(self, span: SourceSpan) -> InstructionWithLocation {
+        InstructionWithLocation { instruction: self, span: Some(span) }
+    }
+}
+impl From<Instruction> for InstructionWithLocation {
+    fn from(instruction: Instruction) -> Self {
+        Self { instruction, span: None }
+    }
+}
+impl Display for InstructionWithLocation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&self.instruction, f)
+    }
+}
+
+/// An instruction along with an optional name for it, such as the name of the function it is the
+/// entry point of.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct LabeledInstruction {
+    pub instruction: Instruction,
+    pub label: Option<String>,
+}
+impl Instruction {
+    /// Attaches a name to the instruction.
+    pub fn with_label(self, label: impl Into<String>) -> LabeledInstruction {
+        LabeledInstruction { instruction: self, label: Some(label.into()) }
+    }
+}
+impl From<Instruction> for LabeledInstruction {
+    fn from(instruction: Instruction) -> Self {
+        Self { instruction, label: None }
+    }
+}
+/// Formats the instruction, preceded by a `NAME:` line if it has a label.
+impl Display for LabeledInstruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(f, "{label}:")?;
+        }
+        Display::fmt(&self.instruction, f)
+    }
+}
+
 /// Represents a call instruction "call rel/abs target".
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct CallInstruction {
     pub target: DerefOrImmediate,
     pub relative: bool,
@@ -101,10 +887,43 @@ impl CallInstruction {
             DerefOrImmediate::Immediate(_) => 2,
         }
     }
+
+    /// Returns the immediate target of the call, which is an offset for a relative call and an
+    /// address for an absolute one, if it is an immediate that fits in an i64.
+    pub fn immediate_offset(&self) -> Option<i64> {
+        self.target.as_immediate()?.to_i64()
+    }
+
+    /// Returns the relative call equivalent to this absolute call, as done for jumps by
+    /// [JumpInstruction::to_relative].
+    pub fn to_relative(&self, self_offset: usize, program: &[usize]) -> Option<CallInstruction> {
+        if self.relative {
+            return None;
+        }
+        let target = absolute_to_relative(&self.target, self_offset, program)?;
+        Some(CallInstruction { target, relative: true })
+    }
+
+    /// Returns the instruction "call rel offset".
+    pub fn rel(offset: i64) -> InstructionBody {
+        InstructionBody::Call(Self { target: DerefOrImmediate::from(offset), relative: true })
+    }
+
+    /// Returns the instruction "call abs address".
+    pub fn abs(address: i64) -> InstructionBody {
+        InstructionBody::Call(Self { target: DerefOrImmediate::from(address), relative: false })
+    }
+
+    /// Returns the instruction "call rel cell", calling with the offset held in `cell`.
+    pub fn indirect(cell: CellRef) -> InstructionBody {
+        InstructionBody::Call(Self { target: DerefOrImmediate::Deref(cell), relative: true })
+    }
 }
 
 /// Represents the InstructionBody "jmp rel/abs target".
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct JumpInstruction {
     pub target: DerefOrImmediate,
     pub relative: bool,
@@ -116,6 +935,27 @@ impl JumpInstruction {
             DerefOrImmediate::Immediate(_) => 2,
         }
     }
+
+    /// Returns the immediate target of the jump, as done for calls by
+    /// [CallInstruction::immediate_offset].
+    pub fn immediate_offset(&self) -> Option<i64> {
+        self.target.as_immediate()?.to_i64()
+    }
+
+    /// Returns the relative jump equivalent to this absolute jump, given the word offset of the
+    /// jump and the offsets of the instructions of the program, as returned by
+    /// [crate::program::instruction_offsets]. The absolute target is taken to be an offset within
+    /// the program.
+    ///
+    /// Returns None if the jump is not an absolute jump with an immediate target, or if the target
+    /// is not the start of an instruction of the program.
+    pub fn to_relative(&self, self_offset: usize, program: &[usize]) -> Option<JumpInstruction> {
+        if self.relative {
+            return None;
+        }
+        let target = absolute_to_relative(&self.target, self_offset, program)?;
+        Some(JumpInstruction { target, relative: true })
+    }
 }
 impl Display for JumpInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -124,7 +964,9 @@ impl Display for JumpInstruction {
 }
 
 /// Represents the InstructionBody "jmp rel <jump_offset> if condition != 0".
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct JnzInstruction {
     pub jump_offset: DerefOrImmediate,
     pub condition: CellRef,
@@ -136,6 +978,29 @@ impl JnzInstruction {
             DerefOrImmediate::Immediate(_) => 2,
         }
     }
+
+    /// Returns the instruction "jmp rel offset if condition != 0".
+    pub fn rel(offset: i64, condition: CellRef) -> InstructionBody {
+        InstructionBody::Jnz(Self { jump_offset: DerefOrImmediate::from(offset), condition })
+    }
+
+    /// Returns the instruction "jmp rel cell if condition != 0", jumping by the offset held in
+    /// `cell`.
+    pub fn indirect(cell: CellRef, condition: CellRef) -> InstructionBody {
+        InstructionBody::Jnz(Self { jump_offset: DerefOrImmediate::Deref(cell), condition })
+    }
+
+    /// Checks that the jnz does not violate any of the invariants described by [JnzError].
+    pub fn validate(&self) -> Result<(), JnzError> {
+        match &self.jump_offset {
+            DerefOrImmediate::Immediate(imm) if imm.value.is_zero() => Err(JnzError::SelfBranch),
+            DerefOrImmediate::Immediate(imm) if imm.value.is_one() => Err(JnzError::IntoImmediate),
+            DerefOrImmediate::Deref(cell) if *cell == self.condition => {
+                Err(JnzError::ConditionIsOffset)
+            }
+            _ => Ok(()),
+        }
+    }
 }
 impl Display for JnzInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -157,7 +1022,9 @@ pub fn op_size_based_on_res_operands(operand: &ResOperand) -> usize {
 }
 
 /// Represents the InstructionBody "a = b" for two operands a, b.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct AssertEqInstruction {
     pub a: CellRef,
     pub b: ResOperand,
@@ -166,6 +1033,30 @@ impl AssertEqInstruction {
     pub fn op_size(&self) -> usize {
         op_size_based_on_res_operands(&self.b)
     }
+
+    /// Returns the instruction "dst = lhs + rhs".
+    pub fn add(dst: CellRef, lhs: CellRef, rhs: DerefOrImmediate) -> InstructionBody {
+        Self::bin_op(Operation::Add, dst, lhs, rhs)
+    }
+
+    /// Returns the instruction "dst = lhs * rhs".
+    pub fn mul(dst: CellRef, lhs: CellRef, rhs: DerefOrImmediate) -> InstructionBody {
+        Self::bin_op(Operation::Mul, dst, lhs, rhs)
+    }
+
+    /// Returns the instruction "dst = src".
+    pub fn deref(dst: CellRef, src: CellRef) -> InstructionBody {
+        InstructionBody::AssertEq(Self { a: dst, b: ResOperand::Deref(src) })
+    }
+
+    /// Returns the instruction "dst = value".
+    pub fn immediate(dst: CellRef, value: impl Into<BigIntAsHex>) -> InstructionBody {
+        InstructionBody::AssertEq(Self { a: dst, b: ResOperand::Immediate(value.into()) })
+    }
+
+    fn bin_op(op: Operation, dst: CellRef, lhs: CellRef, rhs: DerefOrImmediate) -> InstructionBody {
+        InstructionBody::AssertEq(Self { a: dst, b: ResOperand::bin_op(op, lhs, rhs) })
+    }
 }
 impl Display for AssertEqInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -173,8 +1064,35 @@ impl Display for AssertEqInstruction {
     }
 }
 
+/// Represents the InstructionBody "a = b" for two operands a, b, computed over the QM31 extension
+/// field.
+///
+/// This is a distinct type from [AssertEqInstruction], so that an assert over QM31 can not be
+/// mistaken for one over the base field.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize), serde(transparent))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema), schemars(transparent))]
+pub struct QM31AssertEqInstruction(pub AssertEqInstruction);
+impl QM31AssertEqInstruction {
+    pub fn op_size(&self) -> usize {
+        self.0.op_size()
+    }
+}
+impl From<AssertEqInstruction> for QM31AssertEqInstruction {
+    fn from(insn: AssertEqInstruction) -> Self {
+        Self(insn)
+    }
+}
+impl Display for QM31AssertEqInstruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{{QM31}} {}", self.0)
+    }
+}
+
 /// Represents a return instruction, "ret".
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct RetInstruction {}
 impl Display for RetInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -189,7 +1107,9 @@ impl RetInstruction {
 }
 
 /// Represents the InstructionBody "ap += op" for a given operand op.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct AddApInstruction {
     pub operand: ResOperand,
 }
@@ -197,6 +1117,11 @@ impl AddApInstruction {
     pub fn op_size(&self) -> usize {
         op_size_based_on_res_operands(&self.operand)
     }
+
+    /// Returns the instruction "ap += n".
+    pub fn by_immediate(n: BigInt) -> Self {
+        Self { operand: ResOperand::Immediate(n.into()) }
+    }
 }
 impl Display for AddApInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -205,17 +1130,39 @@ impl Display for AddApInstruction {
 }
 
 /// Represents a blake2s instruction, "blake2s".
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Blake2sCompressInstruction {
     pub state: CellRef,
     pub byte_count: CellRef,
     pub message: CellRef,
+    /// Whether the message is the last block to compress, as given by [Self::mode].
     pub finalize: bool,
 }
 impl Blake2sCompressInstruction {
+    /// All the operands of a blake2s instruction are cell references, so it never has an
+    /// immediate word.
     pub fn op_size(&self) -> usize {
         1
     }
+
+    /// Returns the cell references of the instruction, in the order `state`, `byte_count`,
+    /// `message`.
+    pub fn cell_refs(&self) -> [CellRef; 3] {
+        [self.state, self.byte_count, self.message]
+    }
+
+    /// Returns mutable references to the cell references of the instruction, in the same order as
+    /// [Self::cell_refs].
+    pub fn cell_refs_mut(&mut self) -> [&mut CellRef; 3] {
+        [&mut self.state, &mut self.byte_count, &mut self.message]
+    }
+
+    /// Returns whether the instruction compresses an intermediate block or the last one.
+    pub fn mode(&self) -> Blake2sMode {
+        self.finalize.into()
+    }
 }
 impl Display for Blake2sCompressInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -226,3 +1173,62 @@ impl Display for Blake2sCompressInstruction {
         )
     }
 }
+
+/// The kind of compression done by a blake2s instruction.
+///
+/// The textual form of the instruction keeps using the `finalize` flag, where `finalize=true`
+/// stands for [Blake2sMode::Finalize].
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum Blake2sMode {
+    /// Compresses an intermediate block of the message.
+    Compress,
+    /// Compresses the last block of the message, which is padded up to the block size.
+    Finalize,
+}
+impl Display for Blake2sMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Blake2sMode::Compress => write!(f, "compress"),
+            Blake2sMode::Finalize => write!(f, "finalize"),
+        }
+    }
+}
+impl From<bool> for Blake2sMode {
+    /// Converts the `finalize` flag of a blake2s instruction into its mode.
+    fn from(finalize: bool) -> Self {
+        if finalize { Blake2sMode::Finalize } else { Blake2sMode::Compress }
+    }
+}
+impl From<Blake2sMode> for bool {
+    /// Converts the mode of a blake2s instruction into its `finalize` flag.
+    fn from(mode: Blake2sMode) -> Self {
+        mode == Blake2sMode::Finalize
+    }
+}
+
+/// Represents a keccak instruction, "keccak".
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct KeccakInstruction {
+    pub state: CellRef,
+    pub byte_count: CellRef,
+    pub message: CellRef,
+    pub finalize: bool,
+}
+impl KeccakInstruction {
+    /// All the operands of a keccak instruction are cell references, so it never has an
+    /// immediate word.
+    pub fn op_size(&self) -> usize {
+        1
+    }
+}
+impl Display for KeccakInstruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "keccak[state={}, message={}, byte_count={}, finalize={}] => [ap + 0]",
+            self.state, self.message, self.byte_count, self.finalize
+        )
+    }
+}
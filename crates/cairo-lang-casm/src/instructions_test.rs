@@ -1,15 +1,34 @@
 #[cfg(not(feature = "std"))]
-use alloc::{string::ToString, vec};
+use alloc::{format, string::ToString, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, RandomState};
 
 use indoc::indoc;
+use num_bigint::BigInt;
 use test_log::test;
 
-use crate::hints::CoreHint;
+use crate::hints::{CoreHint, Hint};
 use crate::instructions::{
-    AddApInstruction, AssertEqInstruction, CallInstruction, Instruction, InstructionBody,
-    JnzInstruction, JumpInstruction, RetInstruction,
+    AddApInstruction, AssertEqInstruction, Blake2sCompressInstruction, Blake2sMode,
+    CallInstruction, Instruction, InstructionBody, InstructionError, InstructionWithLocation,
+    JnzError, JnzInstruction, JumpInstruction, KeccakInstruction, OffsetDisplay, OffsetOverflow,
+    QM31AssertEqInstruction, RetInstruction, SizeError, SourceSpan, check_op_size,
+    has_hint_terminator,
 };
-use crate::operand::{CellRef, DerefOrImmediate, Register, ResOperand};
+use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+
+/// Asserts that the instruction is displayed as `expected`, up to whitespace, showing the
+/// differing tokens on mismatch.
+fn assert_instr_display(instr: &Instruction, expected: &str) {
+    let actual = instr.to_string();
+    pretty_assertions::assert_eq!(
+        actual.split_whitespace().collect::<Vec<_>>(),
+        expected.split_whitespace().collect::<Vec<_>>(),
+        "unexpected display of `{actual}`"
+    );
+}
 
 #[test]
 fn test_jump_format() {
@@ -21,7 +40,7 @@ fn test_jump_format() {
         false,
     );
 
-    assert_eq!(abs_jmp_insn.to_string(), "jmp abs 3");
+    assert_instr_display(&abs_jmp_insn, "jmp abs 3");
 
     let rel_jmp_insn = Instruction::new(
         InstructionBody::Jump(JumpInstruction {
@@ -31,7 +50,7 @@ fn test_jump_format() {
         true,
     );
 
-    assert_eq!(rel_jmp_insn.to_string(), "jmp rel -5, ap++");
+    assert_instr_display(&rel_jmp_insn, "jmp rel -5, ap++");
 }
 
 #[test]
@@ -67,6 +86,101 @@ fn test_assert_eq_format() {
     assert_eq!(insn.to_string(), "[ap + 5] = 205");
 }
 
+#[test]
+fn test_assert_eq_constructors() {
+    let dst = CellRef { register: Register::AP, offset: 0 };
+    let lhs = CellRef { register: Register::FP, offset: -3 };
+    let rhs = CellRef { register: Register::AP, offset: 2 };
+
+    assert_eq!(
+        AssertEqInstruction::add(dst, lhs, DerefOrImmediate::from(rhs)).to_string(),
+        "[ap + 0] = [fp + -3] + [ap + 2]"
+    );
+    assert_eq!(
+        AssertEqInstruction::mul(dst, lhs, DerefOrImmediate::from(7)).to_string(),
+        "[ap + 0] = [fp + -3] * 7"
+    );
+    assert_eq!(AssertEqInstruction::deref(dst, lhs).to_string(), "[ap + 0] = [fp + -3]");
+    assert_eq!(AssertEqInstruction::immediate(dst, -12).to_string(), "[ap + 0] = -12");
+}
+
+#[test]
+fn test_call_constructors() {
+    assert_eq!(CallInstruction::rel(5).to_string(), "call rel 5");
+    assert_eq!(CallInstruction::rel(-5).to_string(), "call rel -5");
+    assert_eq!(CallInstruction::abs(5).to_string(), "call abs 5");
+    assert_eq!(
+        CallInstruction::indirect(CellRef { register: Register::FP, offset: 0 }).to_string(),
+        "call rel [fp + 0]"
+    );
+}
+
+#[test]
+fn test_jnz_validate() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let jnz = |jump_offset, condition| JnzInstruction { jump_offset, condition };
+
+    assert_eq!(jnz(DerefOrImmediate::from(2), ap(-1)).validate(), Ok(()));
+    assert_eq!(jnz(DerefOrImmediate::from(-3), ap(-1)).validate(), Ok(()));
+    assert_eq!(jnz(DerefOrImmediate::from(ap(-2)), ap(-1)).validate(), Ok(()));
+
+    assert_eq!(jnz(DerefOrImmediate::from(0), ap(-1)).validate(), Err(JnzError::SelfBranch));
+    assert_eq!(jnz(DerefOrImmediate::from(1), ap(-1)).validate(), Err(JnzError::IntoImmediate));
+    assert_eq!(
+        jnz(DerefOrImmediate::from(ap(-1)), ap(-1)).validate(),
+        Err(JnzError::ConditionIsOffset)
+    );
+}
+
+#[test]
+fn test_to_relative() {
+    let program = [0, 2, 3, 5, 7];
+    let abs_jump = JumpInstruction { target: DerefOrImmediate::from(2), relative: false };
+    assert_eq!(
+        abs_jump.to_relative(5, &program),
+        Some(JumpInstruction { target: DerefOrImmediate::from(-3), relative: true })
+    );
+    assert_eq!(
+        abs_jump.to_relative(0, &program),
+        Some(JumpInstruction { target: DerefOrImmediate::from(2), relative: true })
+    );
+    // Not the start of an instruction.
+    let mid_jump = JumpInstruction { target: DerefOrImmediate::from(4), relative: false };
+    assert_eq!(mid_jump.to_relative(0, &program), None);
+    let rel_jump = JumpInstruction { target: DerefOrImmediate::from(2), relative: true };
+    assert_eq!(rel_jump.to_relative(0, &program), None);
+    let deref_jump = JumpInstruction {
+        target: DerefOrImmediate::from(CellRef { register: Register::FP, offset: -3 }),
+        relative: false,
+    };
+    assert_eq!(deref_jump.to_relative(0, &program), None);
+
+    let abs_call = CallInstruction { target: DerefOrImmediate::from(7), relative: false };
+    assert_eq!(
+        abs_call.to_relative(3, &program),
+        Some(CallInstruction { target: DerefOrImmediate::from(4), relative: true })
+    );
+}
+
+#[test]
+fn test_qm31_assert_eq_format() {
+    let dst = CellRef { register: Register::AP, offset: 0 };
+    let lhs = CellRef { register: Register::FP, offset: -3 };
+    for (op, op_str) in [(Operation::Add, "+"), (Operation::Mul, "*")] {
+        let insn = AssertEqInstruction {
+            a: dst,
+            b: ResOperand::BinOp(BinOpOperand { op, a: lhs, b: DerefOrImmediate::from(5) }),
+        };
+        let base = InstructionBody::AssertEq(insn.clone());
+        let qm31 = InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn));
+        assert!(!base.is_qm31());
+        assert!(qm31.is_qm31());
+        assert_eq!(base.to_string(), format!("[ap + 0] = [fp + -3] {op_str} 5"));
+        assert_eq!(qm31.to_string(), format!("{{QM31}} [ap + 0] = [fp + -3] {op_str} 5"));
+        assert_eq!(base.op_size(), qm31.op_size());
+    }
+}
+
 #[test]
 fn test_ret_format() {
     let insn = RetInstruction {};
@@ -82,6 +196,41 @@ fn test_add_ap_format() {
     assert_eq!(addap_insn.to_string(), "ap += 205");
 }
 
+#[test]
+fn test_add_ap_by_immediate() {
+    let by_immediate = AddApInstruction::by_immediate(BigInt::from(5));
+    assert_eq!(by_immediate, AddApInstruction { operand: ResOperand::from(5) });
+    assert_eq!(by_immediate.to_string(), "ap += 5");
+    assert_eq!(by_immediate.op_size(), 2);
+    assert_eq!(AddApInstruction::by_immediate(BigInt::from(-3)).to_string(), "ap += -3");
+
+    let by_deref = AddApInstruction {
+        operand: ResOperand::Deref(CellRef { register: Register::FP, offset: -3 }),
+    };
+    assert_eq!(by_deref.to_string(), "ap += [fp + -3]");
+    assert_eq!(by_deref.op_size(), 1);
+}
+
+#[test]
+fn test_keccak_format() {
+    let keccak_insn = Instruction::new(
+        InstructionBody::Keccak(KeccakInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -4 },
+            message: CellRef { register: Register::AP, offset: -1 },
+            finalize: true,
+        }),
+        true,
+    );
+
+    assert_eq!(keccak_insn.body.op_size(), 1);
+    assert_eq!(
+        keccak_insn.to_string(),
+        "keccak[state=[fp + -5], message=[ap + -1], byte_count=[fp + -4], finalize=true] => [ap + \
+         0], ap++"
+    );
+}
+
 #[test]
 fn test_instruction_with_hint() {
     let dst = CellRef { register: Register::AP, offset: 5 };
@@ -102,3 +251,1162 @@ fn test_instruction_with_hint() {
         }
     );
 }
+
+#[test]
+fn test_instruction_with_hints() {
+    let body = InstructionBody::Ret(RetInstruction {});
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let mut pushed = Instruction::new(body.clone(), false);
+    pushed
+        .push_hint(CoreHint::AllocSegment { dst: ap(0) }.into())
+        .push_hint(CoreHint::AllocSegment { dst: ap(1) }.into())
+        .push_hint(CoreHint::AllocSegment { dst: ap(2) }.into());
+    let constructed = Instruction::with_hints(
+        body,
+        false,
+        vec![
+            CoreHint::AllocSegment { dst: ap(0) }.into(),
+            CoreHint::AllocSegment { dst: ap(1) }.into(),
+            CoreHint::AllocSegment { dst: ap(2) }.into(),
+        ],
+    );
+    assert_eq!(pushed, constructed);
+    assert_eq!(
+        constructed.to_string(),
+        indoc! {"
+            %{ memory[ap + 0] = segments.add() %}
+            %{ memory[ap + 1] = segments.add() %}
+            %{ memory[ap + 2] = segments.add() %}
+            ret"
+        }
+    );
+}
+
+#[test]
+fn test_instruction_with_inline_hints() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let mut instruction = Instruction::new(AssertEqInstruction::deref(ap(0), ap(-1)), true);
+    assert_eq!(format!("{instruction:#}"), "[ap + 0] = [ap + -1], ap++");
+    instruction.push_hint(CoreHint::AllocSegment { dst: ap(1) }.into());
+    assert_eq!(
+        format!("{instruction:#}"),
+        "%{ memory[ap + 1] = segments.add() %} [ap + 0] = [ap + -1], ap++"
+    );
+    instruction.push_hint(CoreHint::AllocSegment { dst: ap(2) }.into());
+    assert_eq!(
+        format!("{instruction:#}"),
+        "%{ memory[ap + 1] = segments.add() %} %{ memory[ap + 2] = segments.add() %} [ap + 0] = \
+         [ap + -1], ap++"
+    );
+    // The default form is unchanged.
+    assert_eq!(
+        instruction.to_string(),
+        indoc! {"
+            %{ memory[ap + 1] = segments.add() %}
+            %{ memory[ap + 2] = segments.add() %}
+            [ap + 0] = [ap + -1], ap++"
+        }
+    );
+}
+
+#[test]
+fn test_eq_ignoring_hints() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let body = AssertEqInstruction::deref(ap(0), ap(-1));
+    let with_hint = Instruction::with_hints(
+        body.clone(),
+        true,
+        vec![CoreHint::AllocSegment { dst: ap(1) }.into()],
+    );
+    let with_other_hint = Instruction::with_hints(
+        body.clone(),
+        true,
+        vec![CoreHint::AllocSegment { dst: ap(2) }.into()],
+    );
+    let without_hints = Instruction::new(body.clone(), true);
+    assert!(with_hint.eq_ignoring_hints(&with_other_hint));
+    assert!(with_hint.eq_ignoring_hints(&without_hints));
+    assert_ne!(with_hint, with_other_hint);
+    assert_ne!(with_hint, without_hints);
+
+    assert!(!with_hint.eq_ignoring_hints(&Instruction::new(body, false)));
+    assert!(
+        !with_hint
+            .eq_ignoring_hints(&Instruction::new(AssertEqInstruction::deref(ap(0), ap(-2)), true))
+    );
+}
+
+/// Returns instructions covering all the instruction variants.
+#[cfg(feature = "serde")]
+fn all_variants_instructions() -> Vec<Instruction> {
+    let ctx = crate::casm! {
+        %{ memory[ap + 5] = segments.add() %}
+        [ap + 5] = 205, ap++;
+        [fp + -3] = [ap + 1] * [fp + 2];
+        [ap + 0] = [[fp + 1] + 3];
+        call rel 3;
+        call abs [fp + 1];
+        jmp rel -5, ap++;
+        jmp abs [ap + 2];
+        jmp rel 205 if [ap + 5] != 0;
+        ap += 7;
+        ret;
+    };
+    let mut instructions = ctx.instructions;
+    instructions.push(Instruction::new(
+        InstructionBody::QM31AssertEq(
+            AssertEqInstruction {
+                a: CellRef { register: Register::AP, offset: 0 },
+                b: crate::res!([fp + -4] * [ap + -1]),
+            }
+            .into(),
+        ),
+        false,
+    ));
+    instructions.push(Instruction::new(
+        InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -4 },
+            message: CellRef { register: Register::FP, offset: -3 },
+            finalize: true,
+        }),
+        true,
+    ));
+
+    instructions.push(Instruction::new(
+        InstructionBody::Keccak(KeccakInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -4 },
+            message: CellRef { register: Register::FP, offset: -3 },
+            finalize: false,
+        }),
+        true,
+    ));
+    // The fixture only holds valid instructions.
+    for instruction in &instructions {
+        assert_eq!(instruction.validate(), Ok(()), "{instruction}");
+    }
+    instructions
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    for instruction in all_variants_instructions() {
+        let serialized = serde_json::to_string(&instruction).unwrap();
+        let deserialized: Instruction = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, instruction);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_bincode_round_trip() {
+    // Hints are serialized untagged, which non self-describing formats such as bincode do not
+    // support.
+    let mut instructions = all_variants_instructions();
+    crate::program::strip_hints(&mut instructions);
+    let serialized = bincode::serialize(&instructions).unwrap();
+    let deserialized: Vec<Instruction> = bincode::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, instructions);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_bincode_hints_fail_to_deserialize() {
+    let instructions = all_variants_instructions();
+    assert!(instructions.iter().any(|instruction| !instruction.hints.is_empty()));
+    // Serializing succeeds, but the untagged hints cannot be told apart when deserializing.
+    let serialized = bincode::serialize(&instructions).unwrap();
+    let error = bincode::deserialize::<Vec<Instruction>>(&serialized).unwrap_err();
+    assert!(matches!(*error, bincode::ErrorKind::DeserializeAnyNotSupported), "{error}");
+}
+
+/// Pins the serialized tags of the variants of [InstructionBody], which must not change.
+#[cfg(feature = "serde")]
+#[test]
+fn test_bincode_variant_tags() {
+    let tags: Vec<u32> = all_variants_instructions()
+        .iter()
+        .map(|instruction| {
+            let serialized = bincode::serialize(&instruction.body).unwrap();
+            u32::from_le_bytes(serialized[..4].try_into().unwrap())
+        })
+        .collect();
+    // AddAp: 0, AssertEq: 1, QM31AssertEq: 2, Call: 3, Jnz: 4, Jump: 5, Ret: 6,
+    // Blake2sCompress: 7, Keccak: 8.
+    assert_eq!(tags, [1, 1, 1, 3, 3, 5, 5, 4, 0, 6, 2, 7, 8]);
+}
+
+#[cfg(feature = "schemars")]
+#[test]
+fn test_instruction_body_schema() {
+    let schema = serde_json::to_value(super::instruction_body_schema()).unwrap();
+    let variants: Vec<&str> = schema["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|variant| variant["required"][0].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        variants,
+        [
+            "AddAp",
+            "AssertEq",
+            "QM31AssertEq",
+            "Call",
+            "Jnz",
+            "Jump",
+            "Ret",
+            "Blake2sCompress",
+            "Keccak"
+        ]
+    );
+    for definition in ["AddApInstruction", "AssertEqInstruction", "CellRef", "ResOperand"] {
+        assert!(schema["definitions"].get(definition).is_some(), "{definition}");
+    }
+}
+
+#[test]
+fn test_op_size_matches_encoding() {
+    let ctx = crate::casm! {
+        [ap + 5] = 205, ap++;
+        [fp + -3] = [ap + 1] * [fp + 2];
+        [fp + -3] = [ap + 1] + 2;
+        [ap + 0] = [[fp + 1] + 3];
+        call rel 3;
+        call abs [fp + 1];
+        jmp rel -5, ap++;
+        jmp abs [ap + 2];
+        jmp rel 205 if [ap + 5] != 0;
+        jmp rel [fp + 3] if [ap + 5] != 0;
+        ap += 7;
+        ap += [fp + -1];
+        ret;
+    };
+    let mut instructions = ctx.instructions;
+    for finalize in [false, true] {
+        instructions.push(Instruction::new(
+            InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+                state: CellRef { register: Register::FP, offset: -5 },
+                byte_count: CellRef { register: Register::FP, offset: -4 },
+                message: CellRef { register: Register::AP, offset: -1 },
+                finalize,
+            }),
+            true,
+        ));
+    }
+
+    for instruction in instructions {
+        assert_eq!(
+            instruction.body.op_size(),
+            instruction.encode().unwrap().len(),
+            "{instruction}"
+        );
+    }
+}
+
+#[test]
+fn test_qm31_op_size_matches_encoding() {
+    let dst = CellRef { register: Register::AP, offset: 0 };
+    let lhs = CellRef { register: Register::FP, offset: -3 };
+    for rhs in [
+        DerefOrImmediate::from(CellRef { register: Register::FP, offset: -4 }),
+        DerefOrImmediate::from(5),
+        // A large immediate, such as a packed QM31 value.
+        DerefOrImmediate::from(BigInt::from(1) << 124),
+    ] {
+        let InstructionBody::AssertEq(insn) = AssertEqInstruction::mul(dst, lhs, rhs) else {
+            unreachable!();
+        };
+        let instruction =
+            Instruction::new(InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)), true);
+        assert_eq!(
+            instruction.body.op_size(),
+            instruction.encode().unwrap().len(),
+            "{instruction}"
+        );
+    }
+}
+
+#[test]
+fn test_has_immediate() {
+    let ctx = crate::casm! {
+        [ap + 5] = 205, ap++;
+        [fp + -3] = [ap + 1] + 2;
+        [fp + -3] = [ap + 1] * [fp + 2];
+        [ap + 0] = [[fp + 1] + 3];
+        [ap + 0] = [fp + 1];
+        call rel 3;
+        call abs [fp + 1];
+        jmp rel -5, ap++;
+        jmp abs [ap + 2];
+        jmp rel 205 if [ap + 5] != 0;
+        jmp rel [fp + 3] if [ap + 5] != 0;
+        ap += 7;
+        ap += [fp + -1];
+        ret;
+    };
+    let has_immediate: Vec<bool> =
+        ctx.instructions.iter().map(|instruction| instruction.body.has_immediate()).collect();
+    assert_eq!(
+        has_immediate,
+        [
+            true, true, false, false, false, true, false, true, false, true, false, true, false,
+            false
+        ]
+    );
+
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let InstructionBody::AssertEq(insn) =
+        AssertEqInstruction::mul(fp(0), fp(-3), DerefOrImmediate::from(2))
+    else {
+        unreachable!();
+    };
+    let bodies = [
+        InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)),
+        InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+            state: fp(-5),
+            byte_count: fp(-4),
+            message: fp(-3),
+            finalize: false,
+        }),
+        InstructionBody::Keccak(KeccakInstruction {
+            state: fp(-5),
+            byte_count: fp(-4),
+            message: fp(-3),
+            finalize: false,
+        }),
+    ];
+    assert_eq!(bodies.map(|body| body.has_immediate()), [true, false, false]);
+
+    for instruction in ctx.instructions {
+        assert_eq!(instruction.body.has_immediate(), instruction.body.op_size() == 2);
+    }
+}
+
+#[test]
+fn test_reads_and_writes() {
+    use crate::{casm, deref};
+
+    let ctx = casm! {
+        [fp + -3] = [ap + 1] * [fp + 2], ap++;
+        [ap + 0] = [[fp + 1] + 3];
+        [ap + 2] = 5;
+        ap += [fp + -4];
+        call abs [fp + 1];
+        jmp rel 5;
+        jmp rel [ap + -1] if [fp + 5] != 0;
+        ret;
+    };
+    let bodies: Vec<_> = ctx.instructions.into_iter().map(|instruction| instruction.body).collect();
+    let reads_and_writes: Vec<_> =
+        bodies.iter().map(|body| (body.reads(), body.writes())).collect();
+    assert_eq!(
+        reads_and_writes,
+        vec![
+            (vec![deref!([ap + 1]), deref!([fp + 2])], vec![deref!([fp - 3])]),
+            (vec![deref!([fp + 1])], vec![deref!([ap])]),
+            (vec![], vec![deref!([ap + 2])]),
+            (vec![deref!([fp - 4])], vec![]),
+            (vec![deref!([fp + 1])], vec![deref!([ap]), deref!([ap + 1])]),
+            (vec![], vec![]),
+            (vec![deref!([ap - 1]), deref!([fp + 5])], vec![]),
+            (vec![deref!([fp - 2]), deref!([fp - 1])], vec![]),
+        ]
+    );
+
+    let blake2s = InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+        state: deref!([fp - 5]),
+        byte_count: deref!([ap - 4]),
+        message: deref!([fp - 3]),
+        finalize: false,
+    });
+    assert_eq!(blake2s.reads(), vec![deref!([fp - 5]), deref!([ap - 4]), deref!([fp - 3])]);
+    assert_eq!(blake2s.writes(), vec![deref!([ap])]);
+}
+
+#[test]
+fn test_dedup_hints() {
+    let a: Hint =
+        CoreHint::AllocSegment { dst: CellRef { register: Register::AP, offset: 0 } }.into();
+    let b: Hint =
+        CoreHint::AllocSegment { dst: CellRef { register: Register::AP, offset: 1 } }.into();
+    let mut instruction = Instruction {
+        body: InstructionBody::Ret(RetInstruction {}),
+        inc_ap: false,
+        hints: vec![a.clone(), a.clone(), b.clone(), a.clone()],
+    };
+    instruction.dedup_hints();
+    assert_eq!(instruction.hints, vec![a.clone(), b, a]);
+}
+
+#[test]
+fn test_instruction_with_span() {
+    let instruction = Instruction::new(
+        AssertEqInstruction::deref(
+            CellRef { register: Register::AP, offset: 0 },
+            CellRef { register: Register::FP, offset: -3 },
+        ),
+        true,
+    );
+    let span = SourceSpan { start: 10, end: 24 };
+    let located = instruction.clone().with_span(span);
+    assert_eq!(located.span(), Some(span));
+    assert_eq!(located.instruction(), &instruction);
+    assert_eq!(located.to_string(), instruction.to_string());
+
+    let other = instruction.clone().with_span(SourceSpan { start: 30, end: 44 });
+    assert_ne!(located, other);
+    assert_eq!(located.instruction(), other.instruction());
+
+    let unlocated = InstructionWithLocation::from(instruction);
+    assert_eq!(unlocated.span(), None);
+    assert_eq!(unlocated.instruction(), located.instruction());
+}
+
+#[test]
+fn test_offset_display() {
+    let instructions = [
+        Instruction::new(
+            AssertEqInstruction::immediate(CellRef { register: Register::AP, offset: 0 }, 7),
+            true,
+        ),
+        Instruction::new(
+            AssertEqInstruction::deref(
+                CellRef { register: Register::AP, offset: 0 },
+                CellRef { register: Register::FP, offset: 1 },
+            ),
+            true,
+        ),
+    ];
+    let first = OffsetDisplay(&instructions[0], 3);
+    assert_eq!(first.to_string(), "0x0003: [ap + 0] = 7, ap++");
+    let second = OffsetDisplay(&instructions[1], first.next_offset());
+    assert_eq!(second.to_string(), "0x0005: [ap + 0] = [fp + 1], ap++");
+    assert_eq!(second.next_offset(), 6);
+}
+
+#[test]
+fn test_validate() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let accepted = [
+        InstructionBody::AddAp(AddApInstruction { operand: ResOperand::from(0) }),
+        AssertEqInstruction::deref(ap(0), ap(0)),
+        InstructionBody::QM31AssertEq(
+            AssertEqInstruction {
+                a: ap(0),
+                b: ResOperand::BinOp(BinOpOperand {
+                    op: Operation::Mul,
+                    a: fp(-3),
+                    b: DerefOrImmediate::from(fp(-4)),
+                }),
+            }
+            .into(),
+        ),
+        InstructionBody::Call(CallInstruction {
+            target: DerefOrImmediate::from(0),
+            relative: false,
+        }),
+        InstructionBody::Jump(JumpInstruction {
+            target: DerefOrImmediate::from(ap(0)),
+            relative: true,
+        }),
+        InstructionBody::Jnz(JnzInstruction {
+            jump_offset: DerefOrImmediate::from(-2),
+            condition: ap(-1),
+        }),
+        InstructionBody::Ret(RetInstruction {}),
+        InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+            state: fp(-5),
+            byte_count: fp(-4),
+            message: fp(-3),
+            finalize: false,
+        }),
+        InstructionBody::Keccak(KeccakInstruction {
+            state: fp(-5),
+            byte_count: fp(-4),
+            message: fp(-3),
+            finalize: true,
+        }),
+    ];
+    for body in accepted {
+        assert_eq!(body.validate(), Ok(()), "{body}");
+    }
+
+    let rejected = [
+        (
+            InstructionBody::Call(CallInstruction {
+                target: DerefOrImmediate::from(0),
+                relative: true,
+            }),
+            InstructionError::SelfBranch("target"),
+        ),
+        (
+            InstructionBody::Jump(JumpInstruction {
+                target: DerefOrImmediate::from(0),
+                relative: true,
+            }),
+            InstructionError::SelfBranch("target"),
+        ),
+        (
+            InstructionBody::Jnz(JnzInstruction {
+                jump_offset: DerefOrImmediate::from(0),
+                condition: ap(-1),
+            }),
+            InstructionError::Jnz(JnzError::SelfBranch),
+        ),
+        (
+            InstructionBody::Jnz(JnzInstruction {
+                jump_offset: DerefOrImmediate::from(1),
+                condition: ap(-1),
+            }),
+            InstructionError::Jnz(JnzError::IntoImmediate),
+        ),
+        (
+            InstructionBody::QM31AssertEq(
+                AssertEqInstruction { a: ap(0), b: ResOperand::Deref(fp(-3)) }.into(),
+            ),
+            InstructionError::QM31NonBinOp(ResOperand::Deref(fp(-3))),
+        ),
+        (
+            InstructionBody::QM31AssertEq(
+                AssertEqInstruction { a: ap(0), b: ResOperand::from(5) }.into(),
+            ),
+            InstructionError::QM31NonBinOp(ResOperand::from(5)),
+        ),
+    ];
+    for (body, error) in rejected {
+        assert_eq!(body.validate(), Err(error), "{body}");
+    }
+}
+
+#[test]
+fn test_allows_inc_ap() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let ret = InstructionBody::Ret(RetInstruction {});
+    let assert_eq = AssertEqInstruction::deref(ap(0), ap(-1));
+    assert!(!ret.allows_inc_ap());
+    assert!(assert_eq.allows_inc_ap());
+
+    assert_eq!(Instruction::new(ret.clone(), false).validate(), Ok(()));
+    assert_eq!(Instruction::new(ret, true).validate(), Err(InstructionError::IncApNotAllowed));
+    assert_eq!(Instruction::new(assert_eq, true).validate(), Ok(()));
+
+    let blake2s = InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+        state: CellRef { register: Register::FP, offset: -5 },
+        byte_count: CellRef { register: Register::FP, offset: -4 },
+        message: ap(3),
+        finalize: false,
+    });
+    assert!(blake2s.allows_inc_ap());
+    assert!(blake2s.requires_inc_ap());
+    assert!(!InstructionBody::Ret(RetInstruction {}).requires_inc_ap());
+    assert_eq!(Instruction::new(blake2s.clone(), true).validate(), Ok(()));
+    assert_eq!(
+        Instruction::new(blake2s.clone(), false).validate(),
+        Err(InstructionError::IncApRequired)
+    );
+    // A valid blake2s instruction assembles, and an invalid one fails to.
+    assert!(Instruction::new(blake2s.clone(), true).assemble().is_ok());
+    assert_eq!(
+        Instruction::new(blake2s, false).assemble(),
+        Err(crate::assembler::AssembleError::InvalidIncAp)
+    );
+    // The body is still validated.
+    let jump = InstructionBody::Jump(JumpInstruction {
+        target: DerefOrImmediate::from(0),
+        relative: true,
+    });
+    assert_eq!(
+        Instruction::new(jump, true).validate(),
+        Err(InstructionError::SelfBranch("target"))
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hash() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let make = || {
+        Instruction::with_hints(
+            AssertEqInstruction::add(ap(0), ap(-1), DerefOrImmediate::from(3)),
+            true,
+            vec![CoreHint::AllocSegment { dst: ap(1) }.into()],
+        )
+    };
+    let state = RandomState::new();
+    assert_eq!(state.hash_one(make()), state.hash_one(make()));
+
+    let mut rewrites = HashMap::new();
+    rewrites.insert(make().body, "add");
+    rewrites.insert(AssertEqInstruction::immediate(ap(0), 3), "immediate");
+    assert_eq!(rewrites.get(&make().body), Some(&"add"));
+    assert_eq!(rewrites.get(&AssertEqInstruction::immediate(ap(0), 3)), Some(&"immediate"));
+    assert_eq!(rewrites.get(&AssertEqInstruction::immediate(ap(0), 4)), None);
+
+    let mut instructions = HashMap::new();
+    instructions.insert(make(), 0);
+    assert_eq!(instructions.get(&make()), Some(&0));
+    assert_eq!(instructions.get(&Instruction { inc_ap: false, ..make() }), None);
+}
+
+/// A [core::fmt::Write] sink over a fixed size buffer.
+struct FixedBuffer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+impl<const N: usize> FixedBuffer<N> {
+    fn new() -> Self {
+        Self { buf: [0; N], len: 0 }
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap()
+    }
+}
+impl<const N: usize> core::fmt::Write for FixedBuffer<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.buf.get_mut(self.len..end).ok_or(core::fmt::Error)?.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_write_to() {
+    let instruction = Instruction::new(
+        AssertEqInstruction::mul(
+            CellRef { register: Register::AP, offset: 0 },
+            CellRef { register: Register::FP, offset: -3 },
+            DerefOrImmediate::from(CellRef { register: Register::AP, offset: -1 }),
+        ),
+        true,
+    );
+    let mut buffer = FixedBuffer::<64>::new();
+    instruction.write_to(&mut buffer).unwrap();
+    assert_eq!(buffer.as_str(), "[ap + 0] = [fp + -3] * [ap + -1], ap++");
+    assert_eq!(buffer.as_str(), instruction.to_string());
+
+    // Writing fails once the buffer is full.
+    let mut small_buffer = FixedBuffer::<16>::new();
+    assert_eq!(instruction.write_to(&mut small_buffer), Err(core::fmt::Error));
+}
+
+#[test]
+fn test_rebase_fp() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let assert_eq = |a, b| InstructionBody::AssertEq(AssertEqInstruction { a, b });
+
+    let mut body = assert_eq(
+        fp(-3),
+        ResOperand::BinOp(BinOpOperand {
+            op: Operation::Add,
+            a: ap(2),
+            b: DerefOrImmediate::Deref(fp(4)),
+        }),
+    );
+    assert_eq!(body.rebase_fp(5), Ok(()));
+    assert_eq!(
+        body,
+        assert_eq(
+            fp(2),
+            ResOperand::BinOp(BinOpOperand {
+                op: Operation::Add,
+                a: ap(2),
+                b: DerefOrImmediate::Deref(fp(9)),
+            }),
+        )
+    );
+
+    // The offset within a double deref is not a frame offset.
+    let mut body = assert_eq(ap(0), ResOperand::DoubleDeref(fp(1), 7));
+    assert_eq!(body.rebase_fp(-2), Ok(()));
+    assert_eq!(body, assert_eq(ap(0), ResOperand::DoubleDeref(fp(-1), 7)));
+
+    let mut body = InstructionBody::Jnz(JnzInstruction {
+        jump_offset: DerefOrImmediate::Deref(fp(i16::MAX)),
+        condition: fp(0),
+    });
+    let original = body.clone();
+    assert_eq!(body.rebase_fp(1), Err(OffsetOverflow));
+    assert_eq!(body, original);
+}
+
+#[test]
+fn test_substitute_cell() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let blake = |state, byte_count, message| {
+        InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+            state,
+            byte_count,
+            message,
+            finalize: false,
+        })
+    };
+
+    let body = blake(fp(-3), ap(1), fp(-3));
+    assert_eq!(body.substitute_cell(fp(-3), ap(5)), blake(ap(5), ap(1), ap(5)));
+    assert_eq!(body.substitute_cell(ap(1), fp(0)), blake(fp(-3), fp(0), fp(-3)));
+    // Cells with the same offset relative to another register are not substituted.
+    assert_eq!(body.substitute_cell(ap(-3), ap(7)), body);
+
+    let body = InstructionBody::AssertEq(AssertEqInstruction {
+        a: ap(0),
+        b: ResOperand::DoubleDeref(ap(0), 0),
+    });
+    assert_eq!(
+        body.substitute_cell(ap(0), fp(2)),
+        InstructionBody::AssertEq(AssertEqInstruction {
+            a: fp(2),
+            b: ResOperand::DoubleDeref(fp(2), 0),
+        })
+    );
+}
+
+#[test]
+fn test_blake2s_cell_refs() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let mut insn = Blake2sCompressInstruction {
+        state: fp(-5),
+        byte_count: fp(-4),
+        message: ap(3),
+        finalize: true,
+    };
+    assert_eq!(insn.cell_refs(), [fp(-5), fp(-4), ap(3)]);
+    for cell in insn.cell_refs_mut() {
+        cell.offset += 1;
+    }
+    assert_eq!(
+        insn,
+        Blake2sCompressInstruction {
+            state: fp(-4),
+            byte_count: fp(-3),
+            message: ap(4),
+            finalize: true,
+        }
+    );
+}
+
+#[test]
+fn test_hint_order() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let less_than = |lhs: i16, dst: i16| -> Hint {
+        CoreHint::TestLessThan {
+            lhs: ResOperand::Deref(ap(lhs)),
+            rhs: ResOperand::from(10),
+            dst: ap(dst),
+        }
+        .into()
+    };
+    let body = || AssertEqInstruction::deref(ap(0), ap(1));
+
+    // Independent hints are sorted.
+    let mut instruction =
+        Instruction::with_hints(body(), false, vec![less_than(3, 1), less_than(2, 0)]);
+    assert!(instruction.hints_are_ordered());
+    instruction.sort_hints();
+    assert_eq!(instruction.hints, [less_than(2, 0), less_than(3, 1)]);
+    assert!(instruction.hints_are_ordered());
+
+    // The second hint reads the output of the first, so they keep their order.
+    let dependent = vec![less_than(5, 1), less_than(1, 0)];
+    let mut instruction = Instruction::with_hints(body(), false, dependent.clone());
+    assert!(instruction.hints_are_ordered());
+    instruction.sort_hints();
+    assert_eq!(instruction.hints, dependent);
+
+    // The first hint reads the output of the second.
+    let instruction =
+        Instruction::with_hints(body(), false, vec![less_than(1, 0), less_than(5, 1)]);
+    assert!(!instruction.hints_are_ordered());
+
+    // Hints with effects beyond their cells are never reordered.
+    let alloc: Hint = CoreHint::AllocSegment { dst: ap(7) }.into();
+    let mut instruction =
+        Instruction::with_hints(body(), false, vec![alloc.clone(), less_than(3, 1)]);
+    instruction.sort_hints();
+    assert_eq!(instruction.hints, [alloc, less_than(3, 1)]);
+}
+
+#[test]
+fn test_instruction_body_from() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let add_ap = AddApInstruction { operand: ResOperand::from(3) };
+    assert_eq!(InstructionBody::from(add_ap.clone()), InstructionBody::AddAp(add_ap));
+    // An assert_eq is converted to the base field variant.
+    let assert_eq = AssertEqInstruction { a: ap(0), b: ResOperand::from(5) };
+    assert_eq!(InstructionBody::from(assert_eq.clone()), InstructionBody::AssertEq(assert_eq));
+    let call = CallInstruction { target: DerefOrImmediate::from(3), relative: true };
+    assert_eq!(InstructionBody::from(call.clone()), InstructionBody::Call(call));
+    let jnz = JnzInstruction { jump_offset: DerefOrImmediate::from(3), condition: ap(-1) };
+    assert_eq!(InstructionBody::from(jnz.clone()), InstructionBody::Jnz(jnz));
+    let jump = JumpInstruction { target: DerefOrImmediate::from(3), relative: false };
+    assert_eq!(InstructionBody::from(jump.clone()), InstructionBody::Jump(jump));
+    assert_eq!(InstructionBody::from(RetInstruction {}), InstructionBody::Ret(RetInstruction {}));
+    let blake = Blake2sCompressInstruction {
+        state: ap(-3),
+        byte_count: ap(-2),
+        message: ap(-1),
+        finalize: false,
+    };
+    assert_eq!(InstructionBody::from(blake.clone()), InstructionBody::Blake2sCompress(blake));
+    let keccak =
+        KeccakInstruction { state: ap(-3), byte_count: ap(-2), message: ap(-1), finalize: true };
+    assert_eq!(InstructionBody::from(keccak.clone()), InstructionBody::Keccak(keccak));
+}
+
+#[test]
+fn test_qm31_assert_eq_distinct() {
+    let insn = AssertEqInstruction {
+        a: CellRef { register: Register::AP, offset: 0 },
+        b: ResOperand::from(5),
+    };
+    let qm31 = QM31AssertEqInstruction::from(insn.clone());
+    assert_eq!(qm31.to_string(), "{QM31} [ap + 0] = 5");
+    assert_ne!(InstructionBody::from(qm31.clone()), InstructionBody::from(insn.clone()));
+    assert_eq!(
+        InstructionBody::from(qm31),
+        InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn))
+    );
+}
+
+#[test]
+fn test_nop() {
+    let nop = InstructionBody::nop();
+    assert_eq!(nop.to_string(), "[ap + 0] = [ap + 0]");
+    assert_eq!(nop.op_size(), 1);
+    assert!(nop.is_nop());
+    let ap = CellRef { register: Register::AP, offset: 0 };
+    let fp = CellRef { register: Register::FP, offset: 0 };
+    assert!(!AssertEqInstruction::deref(ap, fp).is_nop());
+    assert!(!AssertEqInstruction::deref(fp, fp).is_nop());
+    assert!(!InstructionBody::AddAp(AddApInstruction { operand: ResOperand::from(0) }).is_nop());
+    assert!(!InstructionBody::Ret(RetInstruction {}).is_nop());
+}
+
+#[test]
+fn test_instruction_rebase_fp() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let instruction = |offset: i16| {
+        Instruction::with_hints(
+            AssertEqInstruction::deref(ap(0), fp(offset)),
+            true,
+            vec![
+                CoreHint::TestLessThan {
+                    lhs: ResOperand::Deref(fp(offset)),
+                    rhs: ResOperand::Deref(ap(-1)),
+                    dst: ap(0),
+                }
+                .into(),
+            ],
+        )
+    };
+
+    let mut rebased = instruction(-3);
+    assert_eq!(rebased.rebase_fp(4), Ok(()));
+    assert_eq!(rebased, instruction(1));
+
+    let mut overflowing = instruction(i16::MIN);
+    assert_eq!(overflowing.rebase_fp(-1), Err(OffsetOverflow));
+    assert_eq!(overflowing, instruction(i16::MIN));
+
+    // The hint overflows while the body does not.
+    let mut overflowing = instruction(i16::MAX);
+    overflowing.body = AssertEqInstruction::deref(ap(0), fp(0));
+    let original = overflowing.clone();
+    assert_eq!(overflowing.rebase_fp(1), Err(OffsetOverflow));
+    assert_eq!(overflowing, original);
+}
+
+#[test]
+fn test_immediate() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let bin_op = |b| ResOperand::BinOp(BinOpOperand { op: Operation::Add, a: fp(-3), b });
+    let with_immediate = [
+        InstructionBody::AddAp(AddApInstruction { operand: ResOperand::from(7) }),
+        AssertEqInstruction::immediate(ap(0), 7),
+        AssertEqInstruction::add(ap(0), fp(-3), DerefOrImmediate::from(7)),
+        InstructionBody::QM31AssertEq(
+            AssertEqInstruction { a: ap(0), b: bin_op(DerefOrImmediate::from(7)) }.into(),
+        ),
+        CallInstruction::rel(7),
+        CallInstruction::abs(7),
+        InstructionBody::Jump(JumpInstruction {
+            target: DerefOrImmediate::from(7),
+            relative: true,
+        }),
+        InstructionBody::Jnz(JnzInstruction {
+            jump_offset: DerefOrImmediate::from(7),
+            condition: ap(-1),
+        }),
+    ];
+    for body in with_immediate {
+        assert_eq!(body.immediate(), Some(&BigInt::from(7)), "{body}");
+    }
+
+    let without_immediate = [
+        InstructionBody::AddAp(AddApInstruction { operand: ResOperand::Deref(fp(-3)) }),
+        AssertEqInstruction::deref(ap(0), fp(-3)),
+        InstructionBody::AssertEq(AssertEqInstruction {
+            a: ap(0),
+            b: ResOperand::DoubleDeref(fp(-3), 7),
+        }),
+        AssertEqInstruction::mul(ap(0), fp(-3), DerefOrImmediate::from(ap(-1))),
+        CallInstruction::indirect(fp(-3)),
+        InstructionBody::Jump(JumpInstruction {
+            target: DerefOrImmediate::from(fp(-3)),
+            relative: false,
+        }),
+        InstructionBody::Jnz(JnzInstruction {
+            jump_offset: DerefOrImmediate::from(fp(-3)),
+            condition: ap(-1),
+        }),
+        InstructionBody::Ret(RetInstruction {}),
+        InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+            state: fp(-5),
+            byte_count: fp(-4),
+            message: fp(-3),
+            finalize: false,
+        }),
+        InstructionBody::Keccak(KeccakInstruction {
+            state: fp(-5),
+            byte_count: fp(-4),
+            message: fp(-3),
+            finalize: false,
+        }),
+    ];
+    for body in without_immediate {
+        assert_eq!(body.immediate(), None, "{body}");
+        assert!(!body.has_immediate(), "{body}");
+    }
+}
+
+#[test]
+fn test_immediate_mut() {
+    let mut jump = InstructionBody::Jump(JumpInstruction {
+        target: DerefOrImmediate::from(5),
+        relative: true,
+    });
+    *jump.immediate_mut().unwrap() -= 12;
+    assert_eq!(jump.to_string(), "jmp rel -7");
+
+    let fp = CellRef { register: Register::FP, offset: -3 };
+    let mut add = AssertEqInstruction::add(fp, fp, DerefOrImmediate::from(1));
+    *jump.immediate_mut().unwrap() = BigInt::from(3);
+    *add.immediate_mut().unwrap() += 1;
+    assert_eq!(jump.to_string(), "jmp rel 3");
+    assert_eq!(add.to_string(), "[fp + -3] = [fp + -3] + 2");
+
+    assert_eq!(CallInstruction::indirect(fp).immediate_mut(), None);
+    assert_eq!(InstructionBody::Ret(RetInstruction {}).immediate_mut(), None);
+}
+
+#[test]
+fn test_relative_target() {
+    let fp = CellRef { register: Register::FP, offset: -3 };
+    let mut jnz = JnzInstruction::rel(5, fp);
+    assert_eq!(jnz.relative_target(), Some(&DerefOrImmediate::from(5)));
+    *jnz.relative_target_mut().unwrap() = DerefOrImmediate::from(fp);
+    assert_eq!(jnz.to_string(), "jmp rel [fp + -3] if [fp + -3] != 0");
+
+    assert_eq!(CallInstruction::rel(3).relative_target(), Some(&DerefOrImmediate::from(3)));
+    assert_eq!(CallInstruction::abs(3).relative_target(), None);
+    assert_eq!(AssertEqInstruction::immediate(fp, 3).relative_target(), None);
+    assert_eq!(InstructionBody::Ret(RetInstruction {}).relative_target_mut(), None);
+}
+
+#[test]
+fn test_blake2s_mode() {
+    assert_eq!(Blake2sMode::from(false), Blake2sMode::Compress);
+    assert_eq!(Blake2sMode::from(true), Blake2sMode::Finalize);
+    assert!(!bool::from(Blake2sMode::Compress));
+    assert!(bool::from(Blake2sMode::Finalize));
+    assert_eq!(Blake2sMode::Compress.to_string(), "compress");
+    assert_eq!(Blake2sMode::Finalize.to_string(), "finalize");
+
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let insn = |mode: Blake2sMode| Blake2sCompressInstruction {
+        state: fp(-5),
+        byte_count: fp(-4),
+        message: fp(-3),
+        finalize: mode.into(),
+    };
+    assert_eq!(insn(Blake2sMode::Compress).mode(), Blake2sMode::Compress);
+    assert_eq!(insn(Blake2sMode::Finalize).mode(), Blake2sMode::Finalize);
+    assert_eq!(
+        insn(Blake2sMode::Finalize).to_string(),
+        "blake2s[state=[fp + -5], message=[fp + -3], byte_count=[fp + -4], finalize=true] => [ap \
+         + 0]"
+    );
+}
+
+#[test]
+fn test_op_size_checked() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let short =
+        InstructionBody::AssertEq(AssertEqInstruction { a: fp(1), b: ResOperand::Deref(fp(-3)) });
+    assert_eq!(short.op_size_checked(), Ok(1));
+    assert_eq!(CallInstruction::rel(4).op_size_checked(), Ok(2));
+    // No instruction body has an illegal size, so the range check is exercised directly.
+    assert_eq!(check_op_size(0), Err(SizeError { size: 0 }));
+    assert_eq!(check_op_size(3), Err(SizeError { size: 3 }));
+    assert_eq!(
+        SizeError { size: 3 }.to_string(),
+        "Instruction of 3 words, where 1 or 2 are expected"
+    );
+}
+
+#[test]
+fn test_may_fail() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let add_ap = |operand| InstructionBody::AddAp(AddApInstruction { operand });
+    assert!(AssertEqInstruction::immediate(fp(1), 5).may_fail());
+    let qm31_add = AssertEqInstruction {
+        a: fp(1),
+        b: ResOperand::BinOp(BinOpOperand { op: Operation::Add, a: fp(2), b: fp(3).into() }),
+    };
+    assert!(InstructionBody::QM31AssertEq(qm31_add.into()).may_fail());
+    assert!(add_ap(ResOperand::double_deref(fp(-3), 1)).may_fail());
+    assert!(!add_ap(ResOperand::from(5)).may_fail());
+    assert!(!add_ap(ResOperand::Deref(fp(-3))).may_fail());
+    assert!(!CallInstruction::rel(4).may_fail());
+    assert!(!InstructionBody::Ret(RetInstruction {}).may_fail());
+}
+
+#[test]
+fn test_immediate_offset() {
+    let fp = CellRef { register: Register::FP, offset: -3 };
+    let call = |target, relative| CallInstruction { target, relative };
+    let jump = |target, relative| JumpInstruction { target, relative };
+    assert_eq!(call(DerefOrImmediate::from(-5), true).immediate_offset(), Some(-5));
+    assert_eq!(call(DerefOrImmediate::from(7), false).immediate_offset(), Some(7));
+    assert_eq!(call(DerefOrImmediate::from(fp), true).immediate_offset(), None);
+    assert_eq!(jump(DerefOrImmediate::from(12), true).immediate_offset(), Some(12));
+    assert_eq!(jump(DerefOrImmediate::from(fp), false).immediate_offset(), None);
+    let huge = DerefOrImmediate::from(BigInt::from(i64::MAX) + 1);
+    assert_eq!(call(huge.clone(), true).immediate_offset(), None);
+    assert_eq!(jump(huge, true).immediate_offset(), None);
+}
+
+#[test]
+fn test_hint_terminator_in_hint_text() {
+    assert!(!has_hint_terminator("memory[ap + 0] = segments.add()"));
+    assert!(has_hint_terminator("print('%}')"));
+    // `%` followed by a space, as in `% PRIME`, is not a terminator.
+    assert!(!has_hint_terminator("memory[ap + 0] = (memory[fp + -3] + 1) % PRIME"));
+
+    // Formatting never fails, and the hints of the instruction have no terminator.
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let instruction = Instruction::with_hints(
+        InstructionBody::Ret(RetInstruction {}),
+        false,
+        vec![
+            CoreHint::AllocSegment { dst: fp(0) }.into(),
+            CoreHint::DivMod {
+                lhs: ResOperand::Deref(fp(-4)),
+                rhs: ResOperand::Deref(fp(-3)),
+                quotient: fp(1),
+                remainder: fp(2),
+            }
+            .into(),
+        ],
+    );
+    assert_eq!(
+        instruction.to_string(),
+        indoc! {"
+            %{ memory[fp + 0] = segments.add() %}
+            %{ (memory[fp + 1], memory[fp + 2]) = divmod(memory[fp + -4], memory[fp + -3]) %}
+            ret"}
+    );
+    assert_eq!(format!("{instruction:#}").matches("%}").count(), 2);
+    assert_eq!(instruction.validate(), Ok(()));
+}
+
+#[test]
+fn test_debug_asm() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let mut instruction = Instruction::new(AssertEqInstruction::immediate(fp(0), 3), true);
+    assert_eq!(instruction.debug_asm(), instruction.to_string());
+    assert_eq!(instruction.debug_asm(), "[fp + 0] = 3, ap++");
+
+    instruction.hints = vec![
+        CoreHint::AllocSegment { dst: fp(0) }.into(),
+        CoreHint::AllocFelt252Dict { segment_arena_ptr: ResOperand::Deref(fp(-3)) }.into(),
+    ];
+    assert_eq!(
+        instruction.debug_asm(),
+        format!(
+            "{} // hints: memory[fp + 0] = segments.add(); if '__dict_manager' not in globals():",
+            instruction.without_hints()
+        )
+    );
+}
+
+#[test]
+fn test_from_bodies() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let bodies = vec![
+        AssertEqInstruction::immediate(fp(0), 3),
+        CallInstruction::rel(3),
+        InstructionBody::Ret(RetInstruction {}),
+    ];
+    let instructions = Instruction::from_bodies(bodies.clone(), false);
+    assert_eq!(instructions.len(), 3);
+    for (instruction, body) in instructions.into_iter().zip(bodies) {
+        assert_eq!(instruction, Instruction::new(body, false));
+    }
+}
+
+#[test]
+fn test_jnz_helpers() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    assert_eq!(JnzInstruction::rel(7, ap(-1)).to_string(), "jmp rel 7 if [ap + -1] != 0");
+    assert_eq!(JnzInstruction::rel(-4, ap(2)).to_string(), "jmp rel -4 if [ap + 2] != 0");
+    assert_eq!(
+        JnzInstruction::indirect(CellRef { register: Register::FP, offset: -3 }, ap(-1))
+            .to_string(),
+        "jmp rel [fp + -3] if [ap + -1] != 0"
+    );
+}
+
+#[test]
+fn test_encoded_hint_len() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let mut instruction = Instruction::new(AssertEqInstruction::immediate(fp(0), 3), true);
+    assert_eq!(instruction.encoded_hint_len(), 0);
+    instruction.push_hint(CoreHint::AllocSegment { dst: fp(0) }.into());
+    instruction.push_hint(CoreHint::AllocSegment { dst: fp(-12) }.into());
+    assert_eq!(
+        instruction.encoded_hint_len(),
+        "memory[fp + 0] = segments.add()".len() + "memory[fp + -12] = segments.add()".len()
+    );
+}
+
+#[test]
+fn test_snapshot_repr() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let blake = Blake2sCompressInstruction {
+        state: fp(-5),
+        byte_count: fp(-4),
+        message: fp(-3),
+        finalize: true,
+    };
+    let instruction = Instruction::with_hints(
+        blake.into(),
+        true,
+        vec![CoreHint::AllocSegment { dst: fp(0) }.into()],
+    );
+    assert_eq!(
+        instruction.snapshot_repr(),
+        "(blake2s[state=[fp + -5], message=[fp + -3], byte_count=[fp + -4], finalize=true] => [ap \
+         + 0], inc_ap: true, hints: 1)"
+    );
+}
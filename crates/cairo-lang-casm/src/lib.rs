@@ -9,8 +9,20 @@ pub mod ap_change;
 pub mod assembler;
 pub mod builder;
 pub mod cell_expression;
+pub mod cfg;
+pub mod const_env;
+pub mod cost;
+pub mod decoder;
 pub mod encoder;
 pub mod hints;
 pub mod inline;
 pub mod instructions;
 pub mod operand;
+pub mod parser;
+pub mod peephole;
+pub mod program;
+#[cfg(feature = "json")]
+pub mod program_json;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod visitor;
@@ -1,12 +1,16 @@
-use core::fmt::Display;
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::fmt::{Display, LowerHex};
 
 use cairo_lang_utils::bigint::BigIntAsHex;
+use num_bigint::BigInt;
+use num_traits::{Euclid, One, Zero};
 
 #[cfg(test)]
 #[path = "operand_test.rs"]
 mod test;
 
-#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -29,7 +33,7 @@ impl Display for Register {
 }
 
 // Represents the rhs operand of an assert equal InstructionBody.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -56,6 +60,134 @@ impl Display for ResOperand {
         }
     }
 }
+/// Formats the operand with its immediates in hex, e.g. `{:#x}` renders `[ap + 1] + 0xff`.
+impl LowerHex for ResOperand {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ResOperand::Deref(_) | ResOperand::DoubleDeref(_, _) => write!(f, "{self}"),
+            ResOperand::Immediate(operand) => LowerHex::fmt(&operand.value, f),
+            ResOperand::BinOp(operand) => LowerHex::fmt(operand, f),
+        }
+    }
+}
+impl ResOperand {
+    /// Returns the operand `[cell + offset]`, dereferencing the address held in `cell`.
+    pub fn double_deref(cell: CellRef, offset: i16) -> ResOperand {
+        ResOperand::DoubleDeref(cell, offset)
+    }
+
+    /// Returns the operand `a op b`.
+    pub fn bin_op(op: Operation, a: CellRef, b: DerefOrImmediate) -> ResOperand {
+        ResOperand::BinOp(BinOpOperand { op, a, b })
+    }
+
+    /// Returns the binary operation, if this is one.
+    pub fn as_bin_op(&self) -> Option<&BinOpOperand> {
+        match self {
+            ResOperand::BinOp(bin_op) => Some(bin_op),
+            _ => None,
+        }
+    }
+
+    /// Returns the cell holding the address and the offset from it, if this is a double deref.
+    pub fn as_double_deref(&self) -> Option<(&CellRef, i16)> {
+        match self {
+            ResOperand::DoubleDeref(cell, offset) => Some((cell, *offset)),
+            _ => None,
+        }
+    }
+
+    /// Returns the cells the operand reads.
+    ///
+    /// For a double deref, this is the cell holding the address, as the cell at the address is
+    /// only known at runtime.
+    pub fn deref_cells(&self) -> Vec<CellRef> {
+        match self {
+            ResOperand::Deref(cell) | ResOperand::DoubleDeref(cell, _) => vec![*cell],
+            ResOperand::Immediate(_) => vec![],
+            ResOperand::BinOp(BinOpOperand { a, b, .. }) => {
+                [Some(*a), b.as_deref().copied()].into_iter().flatten().collect()
+            }
+        }
+    }
+
+    /// Returns the operand as a [DerefOrImmediate], the inverse of the conversion from it, if it
+    /// is a deref or an immediate.
+    pub fn as_deref_or_immediate(&self) -> Option<DerefOrImmediate> {
+        match self {
+            ResOperand::Deref(cell) => Some(DerefOrImmediate::Deref(*cell)),
+            ResOperand::Immediate(imm) => Some(DerefOrImmediate::Immediate(imm.clone())),
+            ResOperand::DoubleDeref(_, _) | ResOperand::BinOp(_) => None,
+        }
+    }
+
+    /// Returns true if the value of the operand does not depend on memory.
+    ///
+    /// This only holds for immediates, as the first operand of a `BinOp` is always a cell.
+    pub fn is_pure_immediate(&self) -> bool {
+        matches!(self, ResOperand::Immediate(_))
+    }
+
+    /// Returns a simpler equivalent operand if this is a `BinOp` with an identity or absorbing
+    /// immediate operand: `x + 0` and `x * 1` simplify to `x`, and `x * 0` simplifies to `0`.
+    ///
+    /// Note that this is not constant folding: the first operand of a `BinOp` is always a cell, so
+    /// a `BinOp` never has two immediate operands.
+    pub fn try_simplify_identity(&self) -> Option<ResOperand> {
+        let ResOperand::BinOp(BinOpOperand { op, a, b: DerefOrImmediate::Immediate(imm) }) = self
+        else {
+            return None;
+        };
+        match op {
+            Operation::Add if imm.value.is_zero() => Some(ResOperand::Deref(*a)),
+            Operation::Mul if imm.value.is_zero() => Some(ResOperand::from(0)),
+            Operation::Mul if imm.value.is_one() => Some(ResOperand::Deref(*a)),
+            Operation::Add | Operation::Mul => None,
+        }
+    }
+
+    /// Returns true if the operands are equal, with their immediates compared modulo `prime`.
+    pub fn eq_mod(&self, other: &ResOperand, prime: &BigInt) -> bool {
+        match (self, other) {
+            (ResOperand::Immediate(a), ResOperand::Immediate(b)) => {
+                eq_mod(&a.value, &b.value, prime)
+            }
+            (
+                ResOperand::BinOp(BinOpOperand { op, a, b: DerefOrImmediate::Immediate(b) }),
+                ResOperand::BinOp(BinOpOperand {
+                    op: other_op,
+                    a: other_a,
+                    b: DerefOrImmediate::Immediate(other_b),
+                }),
+            ) => op == other_op && a == other_a && eq_mod(&b.value, &other_b.value, prime),
+            _ => self == other,
+        }
+    }
+
+    /// Returns the canonical form of the operand, so that equal computations are structurally
+    /// equal.
+    ///
+    /// Both supported operations are commutative, so the cells of a `BinOp` with two cell
+    /// operands are sorted, by the order of [CellRef]. A `BinOp` with an immediate operand is
+    /// already canonical, as an immediate can only be its second operand.
+    pub fn canonicalize(&self) -> ResOperand {
+        match self {
+            ResOperand::BinOp(BinOpOperand { op, a, b: DerefOrImmediate::Deref(b) }) if b < a => {
+                ResOperand::BinOp(BinOpOperand {
+                    op: op.clone(),
+                    a: *b,
+                    b: DerefOrImmediate::Deref(*a),
+                })
+            }
+            _ => self.clone(),
+        }
+    }
+}
+/// Returns true if `a` and `b` are equal modulo `prime`.
+fn eq_mod(a: &BigInt, b: &BigInt, prime: &BigInt) -> bool {
+    ((a - b) % prime).is_zero()
+}
+
 impl From<DerefOrImmediate> for ResOperand {
     fn from(x: DerefOrImmediate) -> Self {
         match x {
@@ -72,7 +204,9 @@ impl<T: Into<BigIntAsHex>> From<T> for ResOperand {
 }
 
 /// Represents an operand of the form [reg + offset].
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+///
+/// Cell references are ordered by register (ap before fp) and then by offset.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -83,6 +217,13 @@ pub struct CellRef {
     pub register: Register,
     pub offset: i16,
 }
+impl CellRef {
+    /// Returns the cell at `delta` cells from this one, relative to the same register, or None if
+    /// the resulting offset does not fit in the offset encoding range.
+    pub fn offset_by(&self, delta: i16) -> Option<CellRef> {
+        Some(CellRef { register: self.register, offset: self.offset.checked_add(delta)? })
+    }
+}
 impl Display for CellRef {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "[{} + {}]", self.register, self.offset)
@@ -94,7 +235,7 @@ pub fn ap_cell_ref(offset: i16) -> CellRef {
     CellRef { register: Register::AP, offset }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -115,6 +256,43 @@ impl Display for DerefOrImmediate {
         }
     }
 }
+/// Formats the operand with its immediate in hex, e.g. `{:#x}` renders `0xff`.
+impl LowerHex for DerefOrImmediate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DerefOrImmediate::Deref(operand) => write!(f, "{operand}"),
+            DerefOrImmediate::Immediate(operand) => LowerHex::fmt(&operand.value, f),
+        }
+    }
+}
+impl DerefOrImmediate {
+    /// Returns the immediate value, if this is an immediate.
+    pub fn as_immediate(&self) -> Option<&BigInt> {
+        match self {
+            DerefOrImmediate::Deref(_) => None,
+            DerefOrImmediate::Immediate(imm) => Some(&imm.value),
+        }
+    }
+
+    /// Returns the dereferenced cell, if this is a deref.
+    pub fn as_deref(&self) -> Option<&CellRef> {
+        match self {
+            DerefOrImmediate::Deref(cell) => Some(cell),
+            DerefOrImmediate::Immediate(_) => None,
+        }
+    }
+
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, DerefOrImmediate::Immediate(_))
+    }
+
+    /// Reduces the value of an immediate into the range `[0, prime)`.
+    pub fn reduce_mod(&mut self, prime: &BigInt) {
+        if let DerefOrImmediate::Immediate(imm) = self {
+            imm.value = imm.value.rem_euclid(prime);
+        }
+    }
+}
 impl<T: Into<BigIntAsHex>> From<T> for DerefOrImmediate {
     fn from(x: T) -> Self {
         DerefOrImmediate::Immediate(x.into())
@@ -126,7 +304,7 @@ impl From<CellRef> for DerefOrImmediate {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -148,7 +326,7 @@ impl Display for Operation {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(
@@ -165,3 +343,10 @@ impl Display for BinOpOperand {
         write!(f, "{} {} {}", self.a, self.op, self.b)
     }
 }
+/// Formats the operand with its immediate in hex, e.g. `{:#x}` renders `[ap + 1] + 0xff`.
+impl LowerHex for BinOpOperand {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} {} ", self.a, self.op)?;
+        LowerHex::fmt(&self.b, f)
+    }
+}
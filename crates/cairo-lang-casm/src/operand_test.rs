@@ -1,9 +1,11 @@
 #[cfg(not(feature = "std"))]
-use alloc::string::ToString;
+use alloc::{format, string::ToString, vec};
 
+use num_bigint::BigInt;
 use test_log::test;
 
 use super::{BinOpOperand, DerefOrImmediate, Operation};
+use crate::instructions::op_size_based_on_res_operands;
 use crate::operand::{CellRef, Register, ResOperand};
 
 #[test]
@@ -35,3 +37,222 @@ fn test_bin_op_format() {
     };
     assert_eq!(bin_op.to_string(), "[fp + -3] * 1400")
 }
+
+#[test]
+fn test_try_simplify_identity() {
+    let bin_op = |op, b: i32| {
+        ResOperand::BinOp(BinOpOperand {
+            op,
+            a: CellRef { register: Register::AP, offset: 1 },
+            b: DerefOrImmediate::from(b),
+        })
+    };
+    let deref = ResOperand::Deref(CellRef { register: Register::AP, offset: 1 });
+
+    assert_eq!(bin_op(Operation::Add, 0).try_simplify_identity(), Some(deref.clone()));
+    assert_eq!(bin_op(Operation::Add, 1).try_simplify_identity(), None);
+    assert_eq!(bin_op(Operation::Add, -1).try_simplify_identity(), None);
+
+    assert_eq!(bin_op(Operation::Mul, 1).try_simplify_identity(), Some(deref.clone()));
+    assert_eq!(bin_op(Operation::Mul, 0).try_simplify_identity(), Some(ResOperand::from(0)));
+    assert_eq!(bin_op(Operation::Mul, -1).try_simplify_identity(), None);
+    assert_eq!(bin_op(Operation::Mul, 2).try_simplify_identity(), None);
+
+    let deref_bin_op = ResOperand::BinOp(BinOpOperand {
+        op: Operation::Mul,
+        a: CellRef { register: Register::AP, offset: 1 },
+        b: DerefOrImmediate::Deref(CellRef { register: Register::FP, offset: 0 }),
+    });
+    assert_eq!(deref_bin_op.try_simplify_identity(), None);
+    assert_eq!(deref.try_simplify_identity(), None);
+    assert_eq!(ResOperand::from(0).try_simplify_identity(), None);
+}
+
+#[test]
+fn test_deref_or_immediate_accessors() {
+    let cell = CellRef { register: Register::FP, offset: -3 };
+    let deref = DerefOrImmediate::from(cell);
+    assert_eq!(deref.as_immediate(), None);
+    assert_eq!(deref.as_deref(), Some(&cell));
+    assert!(!deref.is_immediate());
+
+    let immediate = DerefOrImmediate::from(-7);
+    assert_eq!(immediate.as_immediate(), Some(&BigInt::from(-7)));
+    assert_eq!(immediate.as_deref(), None);
+    assert!(immediate.is_immediate());
+}
+
+#[test]
+fn test_cell_ref_offset_by() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    assert_eq!(fp(-3).offset_by(5), Some(fp(2)));
+    assert_eq!(ap(2).offset_by(-5), Some(ap(-3)));
+    assert_eq!(ap(i16::MAX - 1).offset_by(1), Some(ap(i16::MAX)));
+    assert_eq!(ap(i16::MAX).offset_by(1), None);
+    assert_eq!(fp(i16::MIN + 1).offset_by(-1), Some(fp(i16::MIN)));
+    assert_eq!(fp(i16::MIN).offset_by(-1), None);
+    assert_eq!(fp(i16::MIN).offset_by(i16::MAX), Some(fp(-1)));
+}
+
+#[test]
+fn test_canonicalize() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let bin_op = |op, a, b| ResOperand::BinOp(BinOpOperand { op, a, b });
+
+    for op in [Operation::Add, Operation::Mul] {
+        let canonical = bin_op(op.clone(), ap(0), DerefOrImmediate::from(fp(-3)));
+        let swapped = bin_op(op.clone(), fp(-3), DerefOrImmediate::from(ap(0)));
+        assert_ne!(canonical, swapped);
+        assert_eq!(swapped.canonicalize(), canonical);
+        assert_eq!(canonical.canonicalize(), canonical);
+
+        let swapped_offsets = bin_op(op.clone(), ap(2), DerefOrImmediate::from(ap(-1)));
+        assert_eq!(
+            swapped_offsets.canonicalize(),
+            bin_op(op.clone(), ap(-1), DerefOrImmediate::from(ap(2)))
+        );
+    }
+
+    // Operands that have no other equivalent form are preserved.
+    for operand in [
+        bin_op(Operation::Add, fp(-3), DerefOrImmediate::from(3)),
+        ResOperand::Deref(fp(-3)),
+        ResOperand::DoubleDeref(fp(-3), 1),
+        ResOperand::from(3),
+    ] {
+        assert_eq!(operand.canonicalize(), operand);
+    }
+}
+
+#[test]
+fn test_reduce_mod() {
+    let prime = BigInt::from(101);
+    let reduced = |value: i64| {
+        let mut operand = DerefOrImmediate::from(value);
+        operand.reduce_mod(&prime);
+        operand
+    };
+    assert_eq!(reduced(-1), DerefOrImmediate::from(100));
+    assert_eq!(reduced(-202), DerefOrImmediate::from(0));
+    assert_eq!(reduced(101), DerefOrImmediate::from(0));
+    assert_eq!(reduced(205), DerefOrImmediate::from(3));
+    assert_eq!(reduced(7), DerefOrImmediate::from(7));
+
+    let cell = CellRef { register: Register::FP, offset: -3 };
+    let mut deref = DerefOrImmediate::from(cell);
+    deref.reduce_mod(&prime);
+    assert_eq!(deref, DerefOrImmediate::from(cell));
+}
+
+#[test]
+fn test_cell_ref_ord() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let mut cells = vec![fp(2), ap(3), fp(-5), ap(-1), fp(0), ap(3)];
+    cells.sort();
+    assert_eq!(cells, [ap(-1), ap(3), ap(3), fp(-5), fp(0), fp(2)]);
+    assert!(Register::AP < Register::FP);
+}
+
+#[test]
+fn test_hex_format() {
+    let cell = CellRef { register: Register::FP, offset: -3 };
+    let address = DerefOrImmediate::from(0x800000000000011_u64);
+    assert_eq!(format!("{address:#x}"), "0x800000000000011");
+    assert_eq!(format!("{address:x}"), "800000000000011");
+    assert_eq!(format!("{:#x}", DerefOrImmediate::from(cell)), "[fp + -3]");
+    assert_eq!(format!("{:#x}", ResOperand::from(255)), "0xff");
+    assert_eq!(format!("{:#x}", ResOperand::DoubleDeref(cell, 16)), "[[fp + -3] + 16]");
+    assert_eq!(
+        format!(
+            "{:#x}",
+            ResOperand::BinOp(BinOpOperand {
+                op: Operation::Mul,
+                a: cell,
+                b: DerefOrImmediate::from(4096)
+            })
+        ),
+        "[fp + -3] * 0x1000"
+    );
+}
+
+#[test]
+fn test_is_pure_immediate() {
+    let cell = CellRef { register: Register::FP, offset: -3 };
+    let bin_op = |b| ResOperand::BinOp(BinOpOperand { op: Operation::Add, a: cell, b });
+    assert!(ResOperand::from(7).is_pure_immediate());
+    assert!(!ResOperand::Deref(cell).is_pure_immediate());
+    assert!(!ResOperand::DoubleDeref(cell, 2).is_pure_immediate());
+    assert!(!bin_op(DerefOrImmediate::from(7)).is_pure_immediate());
+    assert!(!bin_op(DerefOrImmediate::from(cell)).is_pure_immediate());
+}
+
+#[test]
+fn test_double_deref() {
+    let cell = CellRef { register: Register::AP, offset: 1 };
+    let operand = ResOperand::double_deref(cell, -2);
+    assert_eq!(operand, ResOperand::DoubleDeref(cell, -2));
+    assert_eq!(operand.to_string(), "[[ap + 1] + -2]");
+    assert_eq!(operand.as_double_deref(), Some((&cell, -2)));
+    assert_eq!(op_size_based_on_res_operands(&operand), 1);
+    assert_eq!(ResOperand::Deref(cell).as_double_deref(), None);
+    assert_eq!(ResOperand::from(3).as_double_deref(), None);
+}
+
+#[test]
+fn test_eq_mod() {
+    let prime = BigInt::from(101);
+    let cell = CellRef { register: Register::FP, offset: -3 };
+    let bin_op =
+        |b: DerefOrImmediate| ResOperand::BinOp(BinOpOperand { op: Operation::Add, a: cell, b });
+    assert!(ResOperand::from(3).eq_mod(&ResOperand::from(3 + 101), &prime));
+    assert!(ResOperand::from(-1).eq_mod(&ResOperand::from(100), &prime));
+    assert!(!ResOperand::from(3).eq_mod(&ResOperand::from(4), &prime));
+    assert!(bin_op(DerefOrImmediate::from(3)).eq_mod(&bin_op(DerefOrImmediate::from(104)), &prime));
+    assert!(!bin_op(DerefOrImmediate::from(3)).eq_mod(&bin_op(DerefOrImmediate::from(4)), &prime));
+    assert!(bin_op(cell.into()).eq_mod(&bin_op(cell.into()), &prime));
+    assert!(!ResOperand::Deref(cell).eq_mod(&ResOperand::from(3), &prime));
+}
+
+#[test]
+fn test_as_deref_or_immediate() {
+    let cell = CellRef { register: Register::AP, offset: 2 };
+    for operand in [DerefOrImmediate::from(cell), DerefOrImmediate::from(-7)] {
+        assert_eq!(ResOperand::from(operand.clone()).as_deref_or_immediate(), Some(operand));
+    }
+    assert_eq!(ResOperand::DoubleDeref(cell, 1).as_deref_or_immediate(), None);
+    let bin_op = BinOpOperand { op: Operation::Add, a: cell, b: DerefOrImmediate::from(1) };
+    assert_eq!(ResOperand::BinOp(bin_op).as_deref_or_immediate(), None);
+}
+
+#[test]
+fn test_deref_cells() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let bin_op = |b| ResOperand::BinOp(BinOpOperand { op: Operation::Mul, a: ap(1), b });
+    assert_eq!(ResOperand::Deref(ap(2)).deref_cells(), [ap(2)]);
+    assert_eq!(ResOperand::DoubleDeref(ap(3), 7).deref_cells(), [ap(3)]);
+    assert_eq!(ResOperand::from(5).deref_cells(), []);
+    assert_eq!(bin_op(DerefOrImmediate::from(ap(-1))).deref_cells(), [ap(1), ap(-1)]);
+    assert_eq!(bin_op(DerefOrImmediate::from(5)).deref_cells(), [ap(1)]);
+}
+
+#[test]
+fn test_bin_op() {
+    let ap = |offset| CellRef { register: Register::AP, offset };
+    let with_cell = ResOperand::bin_op(Operation::Add, ap(1), ap(-2).into());
+    assert_eq!(with_cell.to_string(), "[ap + 1] + [ap + -2]");
+    assert_eq!(
+        with_cell.as_bin_op(),
+        Some(&BinOpOperand { op: Operation::Add, a: ap(1), b: DerefOrImmediate::from(ap(-2)) })
+    );
+    assert_eq!(op_size_based_on_res_operands(&with_cell), 1);
+
+    let with_immediate = ResOperand::bin_op(Operation::Mul, ap(1), DerefOrImmediate::from(3));
+    assert_eq!(with_immediate.to_string(), "[ap + 1] * 3");
+    assert_eq!(with_immediate.as_bin_op().map(|bin_op| &bin_op.op), Some(&Operation::Mul));
+    assert_eq!(op_size_based_on_res_operands(&with_immediate), 2);
+
+    assert_eq!(ResOperand::Deref(ap(1)).as_bin_op(), None);
+}
@@ -0,0 +1,837 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Display;
+use core::str::FromStr;
+
+use num_bigint::BigInt;
+
+use crate::hints::{CoreHint, ExternalHint, Hint, PythonicHint, StarknetHint};
+use crate::instructions::{
+    AddApInstruction, AssertEqInstruction, Blake2sCompressInstruction, CallInstruction,
+    Instruction, InstructionBody, JnzInstruction, JumpInstruction, KeccakInstruction,
+    QM31AssertEqInstruction, RetInstruction,
+};
+use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+
+#[cfg(test)]
+#[path = "parser_test.rs"]
+mod test;
+
+/// The kind of error encountered while parsing casm text.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// The given token was expected, but something else was found.
+    Expected(&'static str),
+    /// A number could not be parsed.
+    InvalidNumber,
+    /// An offset does not fit in an `i16`.
+    OffsetOutOfRange,
+    /// The text of a hint does not match any of the supported hints.
+    UnsupportedHint,
+    /// Extra input was found after a complete instruction.
+    TrailingInput,
+}
+
+/// An error encountered while parsing casm text.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ParseError {
+    /// The byte position in the input of the offending token.
+    pub position: usize,
+    pub kind: ParseErrorKind,
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.kind {
+            ParseErrorKind::Expected(token) => write!(f, "Expected `{token}`")?,
+            ParseErrorKind::InvalidNumber => write!(f, "Invalid number")?,
+            ParseErrorKind::OffsetOutOfRange => write!(f, "Offset out of range")?,
+            ParseErrorKind::UnsupportedHint => write!(f, "Unsupported hint")?,
+            ParseErrorKind::TrailingInput => write!(f, "Unexpected trailing input")?,
+        }
+        write!(f, " at position {}.", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+impl FromStr for Instruction {
+    type Err = ParseError;
+
+    /// Parses an instruction in the format emitted by its `Display` implementation.
+    ///
+    /// The hints of the prelude are parsed as done by the `FromStr` implementation of [Hint].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let instruction = parser.parse_instruction()?;
+        parser.expect_end()?;
+        Ok(instruction)
+    }
+}
+
+impl FromStr for InstructionBody {
+    type Err = ParseError;
+
+    /// Parses an instruction body in the format emitted by its `Display` implementation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let body = parser.parse_body()?;
+        parser.expect_end()?;
+        Ok(body)
+    }
+}
+
+impl FromStr for Hint {
+    type Err = ParseError;
+
+    /// Parses the pythonic text of a hint, as returned by its `get_pythonic_hint`, without the
+    /// enclosing `%{` and `%}`. Surrounding whitespace is ignored.
+    ///
+    /// The text of a cheatcode hint holds none of its operands, so it cannot be parsed, and
+    /// deprecated hints have no pythonic text. The text of a `TestLessThanOrEqualAddress` is the
+    /// same as that of a `TestLessThanOrEqual` unless one of its operands is a binary operation,
+    /// in which case it is parsed as the latter.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hint_text(s)
+            .map_err(|position| ParseError { position, kind: ParseErrorKind::UnsupportedHint })
+    }
+}
+
+/// Parses a program in the format emitted by the `Display` implementation of
+/// [crate::program::Program], with each instruction on its own line, preceded by its hints.
+///
+/// A hint may span several lines, and is attached to the instruction following it.
+pub fn parse_program(text: &str) -> Result<Vec<Instruction>, ParseError> {
+    let mut parser = Parser::new(text);
+    let mut instructions = Vec::new();
+    parser.skip_whitespace();
+    while !parser.rest().is_empty() {
+        instructions.push(parser.parse_instruction()?);
+        parser.expect_line_end()?;
+    }
+    Ok(instructions)
+}
+
+/// A recursive descent parser over casm text.
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    /// Returns the remaining unparsed input.
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError { position: self.position, kind }
+    }
+
+    /// Consumes `token` if it is the next token in the input.
+    ///
+    /// A token ending with a word character only matches if it is not followed by another, so
+    /// that e.g. `ret` does not match the start of `return`.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        match self.rest().strip_prefix(token) {
+            Some(after) if !(token.ends_with(is_word_char) && after.starts_with(is_word_char)) => {
+                self.position += token.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consumes `token`, or fails if it is not the next token in the input.
+    fn expect(&mut self, token: &'static str) -> Result<(), ParseError> {
+        if self.eat(token) { Ok(()) } else { Err(self.error(ParseErrorKind::Expected(token))) }
+    }
+
+    fn expect_end(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.rest().is_empty() { Ok(()) } else { Err(self.error(ParseErrorKind::TrailingInput)) }
+    }
+
+    /// Consumes the whitespace up to the next token, or fails if it does not end the line of the
+    /// previous token.
+    fn expect_line_end(&mut self) -> Result<(), ParseError> {
+        // Trailing whitespace may have already been consumed while looking for optional tokens.
+        let token_end = self.input[..self.position].trim_end().len();
+        self.skip_whitespace();
+        if self.rest().is_empty() || self.input[token_end..self.position].contains('\n') {
+            Ok(())
+        } else {
+            Err(self.error(ParseErrorKind::Expected("end of line")))
+        }
+    }
+
+    /// Parses a possibly negative decimal integer.
+    fn parse_number(&mut self) -> Result<BigInt, ParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let digits_start = usize::from(rest.starts_with('-'));
+        let len = rest[digits_start..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(rest.len(), |len| digits_start + len);
+        let value = BigInt::from_str(&rest[..len])
+            .map_err(|_| self.error(ParseErrorKind::InvalidNumber))?;
+        self.position += len;
+        Ok(value)
+    }
+
+    fn parse_offset(&mut self) -> Result<i16, ParseError> {
+        self.skip_whitespace();
+        let position = self.position;
+        i16::try_from(self.parse_number()?)
+            .map_err(|_| ParseError { position, kind: ParseErrorKind::OffsetOutOfRange })
+    }
+
+    fn parse_register(&mut self) -> Result<Register, ParseError> {
+        if self.eat("ap") {
+            Ok(Register::AP)
+        } else if self.eat("fp") {
+            Ok(Register::FP)
+        } else {
+            Err(self.error(ParseErrorKind::Expected("ap")))
+        }
+    }
+
+    /// Parses the part of a cell reference following the opening bracket.
+    fn parse_cell_ref_tail(&mut self) -> Result<CellRef, ParseError> {
+        let register = self.parse_register()?;
+        let offset = if self.eat("+") {
+            self.parse_offset()?
+        } else if self.eat("-") {
+            self.skip_whitespace();
+            let position = self.position;
+            self.parse_offset()?
+                .checked_neg()
+                .ok_or(ParseError { position, kind: ParseErrorKind::OffsetOutOfRange })?
+        } else {
+            0
+        };
+        self.expect("]")?;
+        Ok(CellRef { register, offset })
+    }
+
+    /// Parses a cell reference of the form `[reg + offset]`.
+    fn parse_cell_ref(&mut self) -> Result<CellRef, ParseError> {
+        self.expect("[")?;
+        self.parse_cell_ref_tail()
+    }
+
+    fn parse_deref_or_immediate(&mut self) -> Result<DerefOrImmediate, ParseError> {
+        if self.eat("[") {
+            Ok(DerefOrImmediate::Deref(self.parse_cell_ref_tail()?))
+        } else {
+            Ok(DerefOrImmediate::Immediate(self.parse_number()?.into()))
+        }
+    }
+
+    fn parse_res_operand(&mut self) -> Result<ResOperand, ParseError> {
+        if !self.eat("[") {
+            return Ok(ResOperand::Immediate(self.parse_number()?.into()));
+        }
+        if self.eat("[") {
+            let inner = self.parse_cell_ref_tail()?;
+            self.expect("+")?;
+            let offset = self.parse_offset()?;
+            self.expect("]")?;
+            return Ok(ResOperand::DoubleDeref(inner, offset));
+        }
+        let a = self.parse_cell_ref_tail()?;
+        let op = if self.eat("+") {
+            Operation::Add
+        } else if self.eat("*") {
+            Operation::Mul
+        } else {
+            return Ok(ResOperand::Deref(a));
+        };
+        let b = self.parse_deref_or_immediate()?;
+        Ok(ResOperand::BinOp(BinOpOperand { op, a, b }))
+    }
+
+    /// Parses the `rel` or `abs` marker, returning whether the jump is relative.
+    fn parse_relative(&mut self) -> Result<bool, ParseError> {
+        if self.eat("rel") {
+            Ok(true)
+        } else if self.eat("abs") {
+            Ok(false)
+        } else {
+            Err(self.error(ParseErrorKind::Expected("rel")))
+        }
+    }
+
+    fn parse_bool(&mut self) -> Result<bool, ParseError> {
+        if self.eat("true") {
+            Ok(true)
+        } else if self.eat("false") {
+            Ok(false)
+        } else {
+            Err(self.error(ParseErrorKind::Expected("true")))
+        }
+    }
+
+    /// Parses the operands of a hash instruction, of the form
+    /// `[state=.., message=.., byte_count=.., finalize=..] => [ap + 0]`.
+    fn parse_hash_operands(&mut self) -> Result<(CellRef, CellRef, CellRef, bool), ParseError> {
+        self.expect("[")?;
+        self.expect("state")?;
+        self.expect("=")?;
+        let state = self.parse_cell_ref()?;
+        self.expect(",")?;
+        self.expect("message")?;
+        self.expect("=")?;
+        let message = self.parse_cell_ref()?;
+        self.expect(",")?;
+        self.expect("byte_count")?;
+        self.expect("=")?;
+        let byte_count = self.parse_cell_ref()?;
+        self.expect(",")?;
+        self.expect("finalize")?;
+        self.expect("=")?;
+        let finalize = self.parse_bool()?;
+        self.expect("]")?;
+        self.expect("=>")?;
+        self.expect("[")?;
+        self.expect("ap")?;
+        self.expect("+")?;
+        self.expect("0")?;
+        self.expect("]")?;
+        Ok((state, message, byte_count, finalize))
+    }
+
+    fn parse_body(&mut self) -> Result<InstructionBody, ParseError> {
+        if self.eat("call") {
+            let relative = self.parse_relative()?;
+            let target = self.parse_deref_or_immediate()?;
+            return Ok(InstructionBody::Call(CallInstruction { target, relative }));
+        }
+        if self.eat("jmp") {
+            self.skip_whitespace();
+            let relative_position = self.position;
+            let relative = self.parse_relative()?;
+            let target = self.parse_deref_or_immediate()?;
+            if !self.eat("if") {
+                return Ok(InstructionBody::Jump(JumpInstruction { target, relative }));
+            }
+            if !relative {
+                return Err(ParseError {
+                    position: relative_position,
+                    kind: ParseErrorKind::Expected("rel"),
+                });
+            }
+            let condition = self.parse_cell_ref()?;
+            self.expect("!=")?;
+            self.expect("0")?;
+            return Ok(InstructionBody::Jnz(JnzInstruction { jump_offset: target, condition }));
+        }
+        if self.eat("ret") {
+            return Ok(InstructionBody::Ret(RetInstruction {}));
+        }
+        if self.eat("blake2s") {
+            let (state, message, byte_count, finalize) = self.parse_hash_operands()?;
+            return Ok(InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+                state,
+                byte_count,
+                message,
+                finalize,
+            }));
+        }
+        if self.eat("keccak") {
+            let (state, message, byte_count, finalize) = self.parse_hash_operands()?;
+            return Ok(InstructionBody::Keccak(KeccakInstruction {
+                state,
+                byte_count,
+                message,
+                finalize,
+            }));
+        }
+        if self.eat("ap") {
+            self.expect("+=")?;
+            let operand = self.parse_res_operand()?;
+            return Ok(InstructionBody::AddAp(AddApInstruction { operand }));
+        }
+        let is_qm31 = self.eat("{QM31}");
+        let a = self.parse_cell_ref()?;
+        self.expect("=")?;
+        let b = self.parse_res_operand()?;
+        let insn = AssertEqInstruction { a, b };
+        Ok(if is_qm31 {
+            InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn))
+        } else {
+            InstructionBody::AssertEq(insn)
+        })
+    }
+
+    fn parse_instruction(&mut self) -> Result<Instruction, ParseError> {
+        let mut hints = Vec::new();
+        while self.eat("%{") {
+            hints.push(self.parse_hint()?);
+        }
+        let body = self.parse_body()?;
+        let inc_ap = if self.eat(",") {
+            self.expect("ap++")?;
+            true
+        } else {
+            false
+        };
+        Ok(Instruction { body, inc_ap, hints })
+    }
+
+    /// Parses a hint following its opening `%{`, up to and including the closing `%}`.
+    fn parse_hint(&mut self) -> Result<Hint, ParseError> {
+        let len =
+            self.rest().find("%}").ok_or_else(|| self.error(ParseErrorKind::Expected("%}")))?;
+        let hint = parse_hint_text(&self.rest()[..len]).map_err(|position| ParseError {
+            position: self.position + position,
+            kind: ParseErrorKind::UnsupportedHint,
+        })?;
+        self.position += len + "%}".len();
+        Ok(hint)
+    }
+
+    /// Parses an operand of the given kind in the pythonic format used within hints.
+    ///
+    /// Returns the possible operands along with the positions they end at, longest first. Only an
+    /// address may have several parses, as in `memory[memory[fp + 1] + 2]`, where the address
+    /// may either be `[fp + 1]`, followed by `+ 2` in the text of the hint, or `[fp + 1] + 2`.
+    fn parse_hint_operand(&mut self, kind: HintOperandKind) -> Vec<(HintOperand, usize)> {
+        let parsed = match kind {
+            HintOperandKind::Cell => self.parse_cell_ref().map(HintOperand::Cell),
+            HintOperandKind::Integer => self.parse_hint_res_operand().map(HintOperand::Res),
+            HintOperandKind::Address => return self.parse_hint_address(),
+        };
+        parsed.map(|operand| vec![(operand, self.position)]).unwrap_or_default()
+    }
+
+    /// Parses a res operand in the pythonic address format used within hints, returning the
+    /// possible parses as done by [Self::parse_hint_operand].
+    fn parse_hint_address(&mut self) -> Vec<(HintOperand, usize)> {
+        if !self.eat("memory") || !self.eat("[") {
+            return vec![];
+        }
+        if self.eat("memory") {
+            let double_deref = self.parse_cell_ref().and_then(|inner| {
+                self.expect("+")?;
+                let offset = self.parse_offset()?;
+                self.expect("]")?;
+                Ok(ResOperand::DoubleDeref(inner, offset))
+            });
+            return double_deref
+                .map(|operand| vec![(HintOperand::Res(operand), self.position)])
+                .unwrap_or_default();
+        }
+        let Ok(a) = self.parse_cell_ref_tail() else {
+            return vec![];
+        };
+        let deref = (HintOperand::Res(ResOperand::Deref(a)), self.position);
+        let op = if self.eat("+") {
+            Operation::Add
+        } else if self.eat("*") {
+            Operation::Mul
+        } else {
+            return vec![deref];
+        };
+        let b = if self.eat("memory") {
+            self.parse_cell_ref().map(DerefOrImmediate::Deref)
+        } else {
+            self.parse_number().map(|value| DerefOrImmediate::Immediate(value.into()))
+        };
+        match b {
+            Ok(b) => {
+                let bin_op = ResOperand::BinOp(BinOpOperand { op, a, b });
+                vec![(HintOperand::Res(bin_op), self.position), deref]
+            }
+            Err(_) => vec![deref],
+        }
+    }
+
+    /// Parses a res operand in the pythonic integer format used within hints.
+    fn parse_hint_res_operand(&mut self) -> Result<ResOperand, ParseError> {
+        if self.eat("(") {
+            self.expect("memory")?;
+            let a = self.parse_cell_ref()?;
+            let op = if self.eat("+") {
+                Operation::Add
+            } else {
+                self.expect("*")?;
+                Operation::Mul
+            };
+            let b = if self.eat("memory") {
+                DerefOrImmediate::Deref(self.parse_cell_ref()?)
+            } else {
+                DerefOrImmediate::Immediate(self.parse_number()?.into())
+            };
+            self.expect(")")?;
+            self.expect("%")?;
+            self.expect("PRIME")?;
+            return Ok(ResOperand::BinOp(BinOpOperand { op, a, b }));
+        }
+        if !self.eat("memory") {
+            return Ok(ResOperand::Immediate(self.parse_number()?.into()));
+        }
+        self.expect("[")?;
+        if !self.eat("memory") {
+            return Ok(ResOperand::Deref(self.parse_cell_ref_tail()?));
+        }
+        let inner = self.parse_cell_ref()?;
+        self.expect("+")?;
+        let offset = self.parse_offset()?;
+        self.expect("]")?;
+        Ok(ResOperand::DoubleDeref(inner, offset))
+    }
+}
+
+/// The form of an operand within the pythonic text of a hint.
+#[derive(Clone, Copy)]
+enum HintOperandKind {
+    /// A cell, as in `memory[ap + 1] = ..`.
+    Cell,
+    /// A res operand used as an integer, as in `(memory[ap + 1] + 3) % PRIME`.
+    Integer,
+    /// A res operand used as an address, as in `memory[ap + 1] + 3`.
+    Address,
+}
+
+/// An operand of a hint, as parsed from its pythonic text.
+#[derive(Clone, PartialEq)]
+enum HintOperand {
+    Cell(CellRef),
+    Res(ResOperand),
+}
+
+/// A source of the operands of a [HintShape], in the order they are requested.
+trait HintOperands {
+    fn cell(&mut self) -> CellRef;
+    fn integer(&mut self) -> ResOperand;
+    fn address(&mut self) -> ResOperand;
+}
+
+/// Builds a hint out of operands of the kinds it requests from the given source.
+///
+/// A shape is used both to find where its operands are in the pythonic text of the hint, by
+/// building the hint of distinct placeholder operands, and to build the hint once its operands
+/// are parsed.
+type HintShape = fn(&mut dyn HintOperands) -> Hint;
+
+/// The shapes of all the hints that can be parsed from their pythonic text, see the `FromStr`
+/// implementation of [Hint].
+const HINT_SHAPES: &[HintShape] = &[
+    |o| CoreHint::AllocSegment { dst: o.cell() }.into(),
+    |o| CoreHint::TestLessThan { lhs: o.integer(), rhs: o.integer(), dst: o.cell() }.into(),
+    |o| CoreHint::TestLessThanOrEqual { lhs: o.integer(), rhs: o.integer(), dst: o.cell() }.into(),
+    |o| {
+        CoreHint::TestLessThanOrEqualAddress { lhs: o.address(), rhs: o.address(), dst: o.cell() }
+            .into()
+    },
+    |o| {
+        CoreHint::WideMul128 { lhs: o.integer(), rhs: o.integer(), high: o.cell(), low: o.cell() }
+            .into()
+    },
+    |o| {
+        CoreHint::DivMod {
+            lhs: o.integer(),
+            rhs: o.integer(),
+            quotient: o.cell(),
+            remainder: o.cell(),
+        }
+        .into()
+    },
+    |o| {
+        CoreHint::Uint256DivMod {
+            dividend0: o.integer(),
+            dividend1: o.integer(),
+            divisor0: o.integer(),
+            divisor1: o.integer(),
+            quotient0: o.cell(),
+            quotient1: o.cell(),
+            remainder0: o.cell(),
+            remainder1: o.cell(),
+        }
+        .into()
+    },
+    |o| {
+        CoreHint::Uint512DivModByUint256 {
+            dividend0: o.integer(),
+            dividend1: o.integer(),
+            dividend2: o.integer(),
+            dividend3: o.integer(),
+            divisor0: o.integer(),
+            divisor1: o.integer(),
+            quotient0: o.cell(),
+            quotient1: o.cell(),
+            quotient2: o.cell(),
+            quotient3: o.cell(),
+            remainder0: o.cell(),
+            remainder1: o.cell(),
+        }
+        .into()
+    },
+    |o| CoreHint::SquareRoot { value: o.integer(), dst: o.cell() }.into(),
+    |o| {
+        CoreHint::Uint256SquareRoot {
+            value_low: o.integer(),
+            value_high: o.integer(),
+            sqrt0: o.cell(),
+            sqrt1: o.cell(),
+            remainder_low: o.cell(),
+            remainder_high: o.cell(),
+            sqrt_mul_2_minus_remainder_ge_u128: o.cell(),
+        }
+        .into()
+    },
+    |o| {
+        CoreHint::LinearSplit {
+            value: o.integer(),
+            scalar: o.integer(),
+            max_x: o.integer(),
+            x: o.cell(),
+            y: o.cell(),
+        }
+        .into()
+    },
+    |o| CoreHint::AllocFelt252Dict { segment_arena_ptr: o.address() }.into(),
+    |o| CoreHint::Felt252DictEntryInit { dict_ptr: o.address(), key: o.integer() }.into(),
+    |o| CoreHint::Felt252DictEntryUpdate { dict_ptr: o.address(), value: o.integer() }.into(),
+    |o| CoreHint::GetSegmentArenaIndex { dict_end_ptr: o.address(), dict_index: o.cell() }.into(),
+    |o| {
+        CoreHint::InitSquashData {
+            dict_accesses: o.address(),
+            ptr_diff: o.integer(),
+            n_accesses: o.integer(),
+            big_keys: o.cell(),
+            first_key: o.cell(),
+        }
+        .into()
+    },
+    |o| CoreHint::GetCurrentAccessIndex { range_check_ptr: o.address() }.into(),
+    |o| CoreHint::ShouldSkipSquashLoop { should_skip_loop: o.cell() }.into(),
+    |o| CoreHint::GetCurrentAccessDelta { index_delta_minus1: o.cell() }.into(),
+    |o| CoreHint::ShouldContinueSquashLoop { should_continue: o.cell() }.into(),
+    |o| CoreHint::GetNextDictKey { next_key: o.cell() }.into(),
+    |o| {
+        CoreHint::AssertLeFindSmallArcs {
+            range_check_ptr: o.address(),
+            a: o.integer(),
+            b: o.integer(),
+        }
+        .into()
+    },
+    |o| CoreHint::AssertLeIsFirstArcExcluded { skip_exclude_a_flag: o.cell() }.into(),
+    |o| CoreHint::AssertLeIsSecondArcExcluded { skip_exclude_b_minus_a: o.cell() }.into(),
+    |o| CoreHint::RandomEcPoint { x: o.cell(), y: o.cell() }.into(),
+    |o| CoreHint::FieldSqrt { val: o.integer(), sqrt: o.cell() }.into(),
+    |o| CoreHint::DebugPrint { start: o.address(), end: o.address() }.into(),
+    |o| CoreHint::AllocConstantSize { size: o.integer(), dst: o.cell() }.into(),
+    |o| {
+        CoreHint::U256InvModN {
+            b0: o.integer(),
+            b1: o.integer(),
+            n0: o.integer(),
+            n1: o.integer(),
+            g0_or_no_inv: o.cell(),
+            g1_option: o.cell(),
+            s_or_r0: o.cell(),
+            s_or_r1: o.cell(),
+            t_or_k0: o.cell(),
+            t_or_k1: o.cell(),
+        }
+        .into()
+    },
+    |o| {
+        CoreHint::EvalCircuit {
+            n_add_mods: o.integer(),
+            add_mod_builtin: o.address(),
+            n_mul_mods: o.integer(),
+            mul_mod_builtin: o.address(),
+        }
+        .into()
+    },
+    |o| StarknetHint::SystemCall { system: o.address() }.into(),
+    |o| ExternalHint::AddRelocationRule { src: o.address(), dst: o.address() }.into(),
+    |o| ExternalHint::WriteRunParam { index: o.integer(), dst: o.cell() }.into(),
+    |o| ExternalHint::AddMarker { start: o.address(), end: o.address() }.into(),
+    |o| ExternalHint::AddTrace { flag: o.integer() }.into(),
+];
+
+/// The offset of the first placeholder cell, chosen so that placeholders are unlikely to collide
+/// with the constants in the texts of hints.
+const PLACEHOLDER_OFFSET: i16 = 30000;
+
+/// Operands of distinct placeholder cells, recording the requested kinds.
+#[derive(Default)]
+struct Placeholders {
+    operands: Vec<(CellRef, HintOperandKind)>,
+}
+impl Placeholders {
+    fn next(&mut self, kind: HintOperandKind) -> CellRef {
+        let offset = PLACEHOLDER_OFFSET + self.operands.len() as i16;
+        let cell = CellRef { register: Register::AP, offset };
+        self.operands.push((cell, kind));
+        cell
+    }
+}
+impl HintOperands for Placeholders {
+    fn cell(&mut self) -> CellRef {
+        self.next(HintOperandKind::Cell)
+    }
+
+    fn integer(&mut self) -> ResOperand {
+        ResOperand::Deref(self.next(HintOperandKind::Integer))
+    }
+
+    fn address(&mut self) -> ResOperand {
+        ResOperand::Deref(self.next(HintOperandKind::Address))
+    }
+}
+
+/// Operands parsed from the pythonic text of a hint, in the order they were requested by the
+/// shape of the hint.
+struct ParsedOperands(<Vec<HintOperand> as IntoIterator>::IntoIter);
+impl HintOperands for ParsedOperands {
+    fn cell(&mut self) -> CellRef {
+        match self.0.next() {
+            Some(HintOperand::Cell(cell)) => cell,
+            _ => unreachable!("Operands are parsed by the kinds requested by the hint shape."),
+        }
+    }
+
+    fn integer(&mut self) -> ResOperand {
+        match self.0.next() {
+            Some(HintOperand::Res(operand)) => operand,
+            _ => unreachable!("Operands are parsed by the kinds requested by the hint shape."),
+        }
+    }
+
+    fn address(&mut self) -> ResOperand {
+        self.integer()
+    }
+}
+
+/// A piece of the text of a hint of a given shape.
+enum TemplatePiece<'a> {
+    /// Text that is the same for all the hints of the shape.
+    Text(&'a str),
+    /// The operand of the given index.
+    Operand(usize),
+}
+
+/// Splits the text of a hint of placeholder operands into the operands and the texts between
+/// them.
+fn template_pieces<'a>(template: &'a str, placeholders: &Placeholders) -> Vec<TemplatePiece<'a>> {
+    let needles: Vec<String> =
+        placeholders.operands.iter().map(|(cell, _)| cell.to_string()).collect();
+    let mut pieces = vec![];
+    let mut rest = template;
+    loop {
+        let next = needles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, needle)| Some((rest.find(needle.as_str())?, index)))
+            .min();
+        let Some((position, index)) = next else {
+            pieces.push(TemplatePiece::Text(rest));
+            return pieces;
+        };
+        // Res operands are displayed as `memory[ap + 30000]`, while cells are displayed as is.
+        let start = match placeholders.operands[index].1 {
+            HintOperandKind::Cell => position,
+            HintOperandKind::Integer | HintOperandKind::Address => position - "memory".len(),
+        };
+        pieces.push(TemplatePiece::Text(&rest[..start]));
+        pieces.push(TemplatePiece::Operand(index));
+        rest = &rest[position + needles[index].len()..];
+    }
+}
+
+/// Matches the text of a hint against the pieces of the text of a hint shape.
+struct TemplateMatcher<'a> {
+    input: &'a str,
+    kinds: Vec<HintOperandKind>,
+    /// The operands matched so far, by index.
+    operands: Vec<Option<HintOperand>>,
+    /// The furthest position in the input that matched the pieces, for error reporting.
+    furthest: usize,
+}
+impl TemplateMatcher<'_> {
+    /// Returns true if the input from `position` on matches `pieces`, assigning the operands.
+    ///
+    /// As an address may have several parses, all of them are tried in turn.
+    fn matches(&mut self, pieces: &[TemplatePiece<'_>], position: usize) -> bool {
+        self.furthest = self.furthest.max(position);
+        let Some((piece, pieces)) = pieces.split_first() else {
+            return position == self.input.len();
+        };
+        match piece {
+            TemplatePiece::Text(text) => {
+                self.input[position..].starts_with(text)
+                    && self.matches(pieces, position + text.len())
+            }
+            TemplatePiece::Operand(index) => {
+                let mut parser = Parser { input: self.input, position };
+                for (operand, end) in parser.parse_hint_operand(self.kinds[*index]) {
+                    match &self.operands[*index] {
+                        // A repeated operand must be the same in all of its occurrences.
+                        Some(assigned) => {
+                            if *assigned == operand && self.matches(pieces, end) {
+                                return true;
+                            }
+                        }
+                        None => {
+                            self.operands[*index] = Some(operand);
+                            if self.matches(pieces, end) {
+                                return true;
+                            }
+                            self.operands[*index] = None;
+                        }
+                    }
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Parses the pythonic text of a hint, ignoring surrounding whitespace.
+///
+/// On failure, returns the furthest position in the text up to which it matches any hint.
+fn parse_hint_text(text: &str) -> Result<Hint, usize> {
+    let start = text.len() - text.trim_start().len();
+    let input = text.trim();
+    let mut furthest = 0;
+    for shape in HINT_SHAPES {
+        let mut placeholders = Placeholders::default();
+        let template = shape(&mut placeholders).get_pythonic_hint();
+        let pieces = template_pieces(template.trim(), &placeholders);
+        let mut matcher = TemplateMatcher {
+            input,
+            kinds: placeholders.operands.iter().map(|(_, kind)| *kind).collect(),
+            operands: vec![None; placeholders.operands.len()],
+            furthest: 0,
+        };
+        let matched = matcher.matches(&pieces, 0);
+        furthest = furthest.max(matcher.furthest);
+        // All the operands of the supported hints appear in their texts.
+        if let (true, Some(operands)) =
+            (matched, matcher.operands.into_iter().collect::<Option<Vec<_>>>())
+        {
+            return Ok(shape(&mut ParsedOperands(operands.into_iter())));
+        }
+    }
+    Err(start + furthest)
+}
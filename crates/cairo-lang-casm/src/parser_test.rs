@@ -0,0 +1,205 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::ToString, vec};
+
+use indoc::indoc;
+use pretty_assertions::assert_eq;
+use test_case::test_case;
+use test_log::test;
+
+use super::{ParseError, ParseErrorKind, parse_program};
+use crate::casm;
+use crate::hints::{CoreHint, Hint, PythonicHint};
+use crate::instructions::{
+    AssertEqInstruction, Blake2sCompressInstruction, Instruction, InstructionBody,
+    KeccakInstruction,
+};
+use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+use crate::program::Program;
+
+#[test]
+fn test_round_trip() {
+    let ctx = casm! {
+        [fp + -5] = 1, ap++;
+        [fp + -5] = [ap + 1] + [fp + -5], ap++;
+        [fp + 5] = [ap + 1] * 2;
+        [ap + 5] = [[ap + 1] + -2];
+        [ap + 5] = [fp + 3];
+        %{ memory[ap + 5] = segments.add() %}
+        %{ memory[ap + 0] = memory[fp + -3] < 45 %}
+        %{ memory[ap + 0] = 13 <= memory[fp + 9] %}
+        call rel 3;
+        call abs [fp + 2];
+        %{ (memory[ap + 0], memory[ap + 1]) = divmod(memory[ap + 9], 50) %}
+        jmp rel -5, ap++;
+        jmp abs [ap + 1];
+        jmp rel 205 if [ap + 5] != 0;
+        jmp rel [fp + -19] if [ap + 17] != 0, ap++;
+        ap += [fp + 4];
+        ap += 7;
+        ret;
+    };
+    let mut instructions = ctx.instructions;
+    instructions.push(Instruction::new(
+        InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -4 },
+            message: CellRef { register: Register::AP, offset: 3 },
+            finalize: false,
+        }),
+        true,
+    ));
+    instructions.push(Instruction::new(
+        InstructionBody::Keccak(KeccakInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -4 },
+            message: CellRef { register: Register::AP, offset: 3 },
+            finalize: true,
+        }),
+        true,
+    ));
+
+    instructions
+        .push(Instruction::new("{QM31} [ap + 0] = [fp + -3] * [ap + -1]".parse().unwrap(), true));
+
+    for instruction in instructions {
+        assert_eq!(instruction.to_string().parse::<Instruction>(), Ok(instruction.clone()));
+        assert_eq!(instruction.body.to_string().parse::<InstructionBody>(), Ok(instruction.body));
+    }
+}
+
+#[test]
+fn test_parse_hints() {
+    let text = indoc! {"
+        %{ memory[ap + 0] = (memory[fp + -3] + 1) % PRIME < memory[memory[fp + 2] + 4] %}
+        %{ memory[fp + 1] = segments.add() %}
+        [ap + 0] = [fp + -3], ap++"
+    };
+    let instruction: Instruction = text.parse().unwrap();
+    assert_eq!(instruction.hints.len(), 2);
+    assert_eq!(instruction.to_string(), text);
+}
+
+#[test_case("{QM31} ap += 3", 7, ParseErrorKind::Expected("["); "qm31 add ap")]
+#[test_case("jmp abs 3 if [ap + 0] != 0", 4, ParseErrorKind::Expected("rel"); "jnz abs")]
+#[test_case("call far 3", 5, ParseErrorKind::Expected("rel"); "bad call kind")]
+#[test_case("[ap + 70000] = 3", 6, ParseErrorKind::OffsetOutOfRange; "offset out of range")]
+#[test_case("[ap + 0] = [fp + 1], ap++, ap++", 25, ParseErrorKind::TrailingInput; "trailing")]
+#[test_case("[sp + 0] = 3", 1, ParseErrorKind::Expected("ap"); "unknown register")]
+#[test_case("ap += x", 6, ParseErrorKind::InvalidNumber; "bad number")]
+#[test_case("%{ print(1) %}\nret", 3, ParseErrorKind::UnsupportedHint; "unsupported hint")]
+#[test_case("%{ memory[ap + 0] = segments.pop() %}\nret", 20, ParseErrorKind::UnsupportedHint; "partial hint")]
+#[test_case("return", 0, ParseErrorKind::Expected("["); "keyword prefix")]
+#[test_case("%{ memory[ap + 0] = segments.add()\nret", 2, ParseErrorKind::Expected("%}"); "open hint")]
+fn test_parse_errors(text: &str, position: usize, kind: ParseErrorKind) {
+    assert_eq!(text.parse::<Instruction>(), Err(ParseError { position, kind }));
+}
+
+#[test]
+fn test_parse_qm31() {
+    let text = "{QM31} [ap + 0] = [fp + -3] + 5";
+    let body: InstructionBody = text.parse().unwrap();
+    assert_eq!(
+        body,
+        InstructionBody::QM31AssertEq(
+            AssertEqInstruction {
+                a: CellRef { register: Register::AP, offset: 0 },
+                b: ResOperand::BinOp(BinOpOperand {
+                    op: Operation::Add,
+                    a: CellRef { register: Register::FP, offset: -3 },
+                    b: DerefOrImmediate::from(5),
+                }),
+            }
+            .into()
+        )
+    );
+    assert_eq!(body.to_string(), text);
+    assert!(!matches!(
+        "[ap + 0] = [fp + -3] + 5".parse::<InstructionBody>(),
+        Ok(InstructionBody::QM31AssertEq(_))
+    ));
+}
+
+#[test]
+fn test_parse_program() {
+    let instructions = casm! {
+        %{ memory[ap + 0] = segments.add() %}
+        %{ memory[ap + 1] = memory[fp + -3] < 45 %}
+        [ap + 2] = [[ap + 0] + 1], ap++;
+        jmp rel 4 if [ap + -1] != 0;
+        ret;
+    }
+    .instructions;
+    let text = Program(instructions.clone()).to_string();
+    assert_eq!(parse_program(&text), Ok(instructions.clone()));
+    // A hint may span lines, and blank lines are skipped.
+    assert_eq!(
+        parse_program(indoc! {"
+            %{
+                memory[ap + 0] = segments.add()
+            %}
+            %{ memory[ap + 1] = memory[fp + -3] < 45 %}
+            [ap + 2] = [[ap + 0] + 1], ap++
+
+            jmp rel 4 if [ap + -1] != 0
+            ret
+        "}),
+        Ok(instructions)
+    );
+    assert_eq!(parse_program(""), Ok(vec![]));
+    assert_eq!(
+        parse_program("ret ret"),
+        Err(ParseError { position: 4, kind: ParseErrorKind::Expected("end of line") })
+    );
+}
+
+#[test]
+fn test_parse_hint() {
+    let hint: Hint = "memory[ap + 0] = segments.add()".parse().unwrap();
+    assert_eq!(hint.get_pythonic_hint(), "memory[ap + 0] = segments.add()");
+    assert_eq!(
+        "memory[ap + 0] = segments.add() + 1".parse::<Hint>(),
+        Err(ParseError { position: 31, kind: ParseErrorKind::UnsupportedHint })
+    );
+    let text = indoc! {"
+
+        (value, scalar) = (memory[ap + -1], 10633823966279327296825105735305134080)
+        x = min(value // scalar, 340282366920938463463374607431768211454)
+        y = value - x * scalar
+        memory[ap + 0] = x
+        memory[ap + 1] = y
+    "};
+    let hint: Hint = text.parse().unwrap();
+    assert_eq!(hint.get_pythonic_hint(), text);
+    // An address may be followed by an offset in the text of the hint.
+    for range_check_ptr in [
+        ResOperand::Deref(CellRef { register: Register::FP, offset: -5 }),
+        ResOperand::BinOp(BinOpOperand {
+            op: Operation::Add,
+            a: CellRef { register: Register::FP, offset: -5 },
+            b: DerefOrImmediate::from(1),
+        }),
+    ] {
+        let hint: Hint = CoreHint::AssertLeFindSmallArcs {
+            range_check_ptr,
+            a: ResOperand::from(3),
+            b: ResOperand::Deref(CellRef { register: Register::AP, offset: -1 }),
+        }
+        .into();
+        assert_eq!(hint.get_pythonic_hint().parse(), Ok(hint));
+    }
+    // The text of a cheatcode does not hold its operands.
+    assert_eq!(
+        r#"raise NotImplementedError("Cheatcode")"#.parse::<Hint>(),
+        Err(ParseError { position: 0, kind: ParseErrorKind::UnsupportedHint })
+    );
+}
+
+#[cfg(feature = "testing")]
+proptest::proptest! {
+    #[test]
+    fn test_parse_hint_round_trip_arbitrary(hint: Hint) {
+        proptest::prop_assert_eq!(hint.get_pythonic_hint().parse::<Hint>(), Ok(hint.clone()));
+        let instruction = Instruction::with_hints("ret".parse().unwrap(), false, vec![hint]);
+        proptest::prop_assert_eq!(instruction.to_string().parse::<Instruction>(), Ok(instruction));
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num_traits::One;
+
+use crate::instructions::{Instruction, InstructionBody};
+use crate::operand::ResOperand;
+use crate::program::{instruction_offsets, program_size, relocate_branches, static_branch_targets};
+
+#[cfg(test)]
+#[path = "peephole_test.rs"]
+mod test;
+
+/// Removes redundant ap updates from the program:
+/// * Adjacent `ap += a` and `ap += b` with immediate operands are fused into `ap += a + b`.
+/// * An assert_eq without ap++ followed by `ap += 1` becomes the assert_eq with ap++, as done by
+///   [fold_ap_increment].
+///
+/// Instructions carrying hints are never optimized, nor are instructions that are branch targets
+/// removed. The offsets of relative branches are updated to account for the removed instructions.
+/// If the targets of some branch are not known statically, the program is left unchanged, as
+/// removing instructions may change where such a branch lands.
+pub fn optimize_peephole(instructions: &mut Vec<Instruction>) {
+    fuse_adjacent(instructions, |prev, next| {
+        try_fuse_add_ap(prev, next) || try_fold_ap_increment(prev, next)
+    });
+}
+
+/// Removes each `ap += 1` that directly follows an assert_eq without ap++, setting ap++ on the
+/// assert_eq instead.
+///
+/// The same restrictions as in [optimize_peephole] apply: no instructions carrying hints or that
+/// are branch targets are removed, and the program is left unchanged if the targets of some branch
+/// are not known statically.
+pub fn fold_ap_increment(instructions: &mut Vec<Instruction>) {
+    fuse_adjacent(instructions, try_fold_ap_increment);
+}
+
+/// Fuses each instruction into the one directly preceding it using `try_fuse`, which returns
+/// whether it fused its second argument into its first.
+fn fuse_adjacent(
+    instructions: &mut Vec<Instruction>,
+    try_fuse: impl Fn(&mut Instruction, &Instruction) -> bool,
+) {
+    let offsets = instruction_offsets(instructions);
+    let size = program_size(instructions);
+    let Some(targets) = static_branch_targets(instructions, &offsets, size) else {
+        return;
+    };
+
+    // The optimized instructions, along with their offsets in the original program.
+    let mut optimized: Vec<(usize, Instruction)> = Vec::with_capacity(instructions.len());
+    for (offset, instruction) in offsets.into_iter().zip(instructions.iter()) {
+        if let Some((_, prev)) = optimized.last_mut() {
+            if targets.binary_search(&offset).is_err()
+                && prev.hints.is_empty()
+                && instruction.hints.is_empty()
+                && try_fuse(prev, instruction)
+            {
+                continue;
+            }
+        }
+        optimized.push((offset, instruction.clone()));
+    }
+
+    if let Some(relocated) = relocate_branches(optimized) {
+        *instructions = relocated;
+    }
+}
+
+/// Tries to fuse `next` into `prev` if both are `ap +=` an immediate. Returns whether it was fused.
+fn try_fuse_add_ap(prev: &mut Instruction, next: &Instruction) -> bool {
+    let (InstructionBody::AddAp(prev_add_ap), InstructionBody::AddAp(next_add_ap)) =
+        (&mut prev.body, &next.body)
+    else {
+        return false;
+    };
+    let (ResOperand::Immediate(prev_imm), ResOperand::Immediate(next_imm)) =
+        (&mut prev_add_ap.operand, &next_add_ap.operand)
+    else {
+        return false;
+    };
+    prev_imm.value += &next_imm.value;
+    true
+}
+
+/// Tries to fold `next` into `prev` if it is `ap += 1` and `prev` is an assert_eq without ap++.
+/// Returns whether it was folded.
+fn try_fold_ap_increment(prev: &mut Instruction, next: &Instruction) -> bool {
+    let InstructionBody::AddAp(next_add_ap) = &next.body else {
+        return false;
+    };
+    let ResOperand::Immediate(next_imm) = &next_add_ap.operand else {
+        return false;
+    };
+    match &prev.body {
+        InstructionBody::AssertEq(_) | InstructionBody::QM31AssertEq(_)
+            if !prev.inc_ap && next_imm.value.is_one() =>
+        {
+            prev.inc_ap = true;
+            true
+        }
+        _ => false,
+    }
+}
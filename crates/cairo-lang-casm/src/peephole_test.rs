@@ -0,0 +1,181 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+use indoc::indoc;
+use pretty_assertions::assert_eq;
+use test_log::test;
+
+use super::{fold_ap_increment, optimize_peephole};
+use crate::casm;
+use crate::instructions::Instruction;
+
+/// Returns the given program as text.
+fn to_text(instructions: &[Instruction]) -> String {
+    instructions.iter().fold(String::new(), |mut text, instruction| {
+        writeln!(text, "{instruction};").unwrap();
+        text
+    })
+}
+
+/// Optimizes the given program and returns the optimized program as text.
+fn optimized(mut instructions: Vec<Instruction>) -> String {
+    optimize_peephole(&mut instructions);
+    to_text(&instructions)
+}
+
+#[test]
+fn test_fuse_add_ap() {
+    let instructions = casm! {
+        ap += 2;
+        ap += 3;
+        ap += [fp + -3];
+        ap += 4;
+        ap += 5;
+        ap += 6;
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        optimized(instructions),
+        indoc! {"
+            ap += 5;
+            ap += [fp + -3];
+            ap += 15;
+            ret;
+        "}
+    );
+}
+
+#[test]
+fn test_fold_inc_ap() {
+    let instructions = casm! {
+        [ap + 0] = [fp + -3];
+        ap += 1;
+        [ap + 0] = 7, ap++;
+        ap += 1;
+        [ap + 0] = [fp + -4];
+        ap += 2;
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        optimized(instructions),
+        indoc! {"
+            [ap + 0] = [fp + -3], ap++;
+            [ap + 0] = 7, ap++;
+            ap += 1;
+            [ap + 0] = [fp + -4];
+            ap += 2;
+            ret;
+        "}
+    );
+}
+
+#[test]
+fn test_relocate_branches() {
+    let instructions = casm! {
+        jmp rel 6 if [fp + -3] != 0;
+        ap += 1;
+        ap += 1;
+        // The target of the `jnz`, so it is not fused into the previous instruction.
+        ap += 1;
+        jmp rel (-8);
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        optimized(instructions),
+        indoc! {"
+            jmp rel 4 if [fp + -3] != 0;
+            ap += 2;
+            ap += 1;
+            jmp rel -6;
+            ret;
+        "}
+    );
+}
+
+#[test]
+fn test_branch_before_start() {
+    let instructions = casm! {
+        ap += 1;
+        ap += 1;
+        jmp rel (-6);
+    }
+    .instructions;
+    assert_eq!(optimized(instructions.clone()), to_text(&instructions));
+}
+
+#[test]
+fn test_hints_block_optimization() {
+    let instructions = casm! {
+        ap += 1;
+        %{ memory[ap + 0] = segments.add() %}
+        ap += 1;
+        [ap + 0] = [fp + -3];
+        %{ memory[ap + 0] = segments.add() %}
+        ap += 1;
+        ret;
+    }
+    .instructions;
+    assert_eq!(optimized(instructions.clone()), to_text(&instructions));
+}
+
+#[test]
+fn test_unknown_targets_block_optimization() {
+    let instructions = casm! {
+        ap += 1;
+        ap += 1;
+        jmp abs [fp + -3];
+    }
+    .instructions;
+    assert_eq!(optimized(instructions.clone()), to_text(&instructions));
+}
+
+#[test]
+fn test_fold_ap_increment() {
+    let mut instructions = casm! {
+        [ap + 0] = [fp + -3];
+        ap += 1;
+        ap += 1;
+        jmp rel 6 if [ap + -1] != 0;
+        [ap + 0] = [ap + -1] * 3;
+        ap += 1;
+        ret;
+    }
+    .instructions;
+    fold_ap_increment(&mut instructions);
+    assert_eq!(
+        to_text(&instructions),
+        indoc! {"
+            [ap + 0] = [fp + -3], ap++;
+            ap += 1;
+            jmp rel 4 if [ap + -1] != 0;
+            [ap + 0] = [ap + -1] * 3, ap++;
+            ret;
+        "}
+    );
+}
+
+#[test]
+fn test_fold_ap_increment_blocked() {
+    let mut instructions = casm! {
+        // The `ap += 1` carries a hint.
+        [ap + 0] = [fp + -3];
+        %{ memory[ap + 0] = segments.add() %}
+        ap += 1;
+        // The `ap += 1` is the target of the `jnz`.
+        jmp rel 3 if [ap + -1] != 0;
+        [ap + 0] = [fp + -4];
+        ap += 1;
+        // The assert_eq already has ap++.
+        [ap + 0] = [fp + -5], ap++;
+        ap += 1;
+        ret;
+    }
+    .instructions;
+    let expected = to_text(&instructions);
+    fold_ap_increment(&mut instructions);
+    assert_eq!(to_text(&instructions), expected);
+}
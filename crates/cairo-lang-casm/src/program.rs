@@ -0,0 +1,754 @@
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use core::fmt::{Display, Write};
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::ap_change::ApChange;
+use crate::cfg::ControlFlow;
+use crate::hints::{Hint, PythonicHint};
+use crate::instructions::{Instruction, InstructionBody, LabeledInstruction};
+use crate::operand::{CellRef, DerefOrImmediate, Register};
+use crate::visitor::OperandVisitor;
+
+#[cfg(test)]
+#[path = "program_test.rs"]
+mod test;
+
+/// A program, displayed with each of its instructions on its own line.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct Program(pub Vec<Instruction>);
+impl Display for Program {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for instruction in &self.0 {
+            writeln!(f, "{instruction}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects instructions while tracking the ap change and the size, in words, of the instructions
+/// pushed so far.
+#[derive(Debug, Clone)]
+pub struct InstructionSink {
+    instructions: Vec<Instruction>,
+    ap_change: ApChange,
+    word_offset: usize,
+}
+impl Default for InstructionSink {
+    fn default() -> Self {
+        Self { instructions: Vec::new(), ap_change: ApChange::Known(0), word_offset: 0 }
+    }
+}
+impl InstructionSink {
+    /// Appends an instruction, applying its ap change.
+    ///
+    /// The ap change becomes unknown after a call, as well as after an `ap += x` whose value is
+    /// not a non-negative immediate.
+    pub fn push(&mut self, instruction: Instruction) {
+        self.word_offset += instruction.body.op_size();
+        if let ApChange::Known(ap_change) = self.ap_change {
+            let body_change = match &instruction.body {
+                InstructionBody::AddAp(_) => {
+                    instruction.body.immediate().and_then(|imm| imm.to_usize())
+                }
+                InstructionBody::Call(_) => None,
+                _ => Some(0),
+            };
+            self.ap_change = match body_change {
+                Some(change) => {
+                    ApChange::Known(ap_change + change + usize::from(instruction.inc_ap))
+                }
+                None => ApChange::Unknown,
+            };
+        }
+        self.instructions.push(instruction);
+    }
+
+    /// Returns the change of ap since the first instruction pushed.
+    pub fn ap_change(&self) -> ApChange {
+        self.ap_change
+    }
+
+    /// Returns the offset, in words, at which the next pushed instruction will start.
+    pub fn word_offset(&self) -> usize {
+        self.word_offset
+    }
+
+    /// Returns the cell, relative to the current ap, which was `[ap + offset]` when the ap change
+    /// was `ap_change`.
+    ///
+    /// Returns None if the current ap change is unknown, or if the cell offset does not fit.
+    pub fn ap_cell(&self, ap_change: usize, offset: i16) -> Option<CellRef> {
+        let ApChange::Known(current) = self.ap_change else {
+            return None;
+        };
+        let delta = i16::try_from(current.checked_sub(ap_change)?).ok()?;
+        Some(CellRef { register: Register::AP, offset: offset.checked_sub(delta)? })
+    }
+
+    /// Returns the instructions pushed so far.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Consumes the sink, returning the instructions pushed into it.
+    pub fn into_instructions(self) -> Vec<Instruction> {
+        self.instructions
+    }
+}
+
+/// Returns the total number of words the given instructions occupy in the encoded program.
+pub fn program_size(instructions: &[Instruction]) -> usize {
+    instructions.iter().map(|instruction| instruction.body.op_size()).sum()
+}
+
+/// The number of bytes in a word of the encoded program, which is a felt252.
+pub const WORD_BYTES: usize = 32;
+
+/// Returns the total number of bytes the given instructions occupy in the encoded program, with
+/// each word taking [WORD_BYTES] bytes.
+pub fn program_byte_size(instructions: &[Instruction]) -> usize {
+    program_size(instructions) * WORD_BYTES
+}
+
+/// Returns the offset, in words, at which each of the given instructions starts in the encoded
+/// program.
+pub fn instruction_offsets(instructions: &[Instruction]) -> Vec<usize> {
+    instructions
+        .iter()
+        .scan(0, |offset, instruction| {
+            let start = *offset;
+            *offset += instruction.body.op_size();
+            Some(start)
+        })
+        .collect()
+}
+
+/// Statistics on the size of a program.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ProgramMetrics {
+    /// The number of words the program occupies in its encoding.
+    pub word_count: usize,
+    pub instruction_count: usize,
+    pub hint_count: usize,
+}
+
+/// Returns statistics on the size of the given program.
+pub fn program_metrics(instructions: &[Instruction]) -> ProgramMetrics {
+    ProgramMetrics {
+        word_count: program_size(instructions),
+        instruction_count: instructions.len(),
+        hint_count: all_hints(instructions).count(),
+    }
+}
+
+/// Removes the hints of all the given instructions.
+pub fn strip_hints(instructions: &mut [Instruction]) {
+    for instruction in instructions {
+        instruction.hints.clear();
+    }
+}
+
+/// The resolved target of a relative branch instruction.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ResolvedTarget {
+    /// The index of the branch instruction in the program.
+    pub index: usize,
+    /// The absolute address the branch lands on.
+    pub target: Result<usize, TargetError>,
+}
+
+/// An error in the target of a relative branch instruction.
+#[derive(Debug, Eq, PartialEq)]
+pub enum TargetError {
+    /// The target is outside of the program.
+    OutOfProgram,
+    /// The target is in the middle of the multi-word instruction at the given index.
+    MidInstruction(usize),
+}
+impl Display for TargetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TargetError::OutOfProgram => write!(f, "Branch target is outside of the program"),
+            TargetError::MidInstruction(index) => {
+                write!(f, "Branch target is in the middle of instruction #{index}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TargetError {}
+
+/// Resolves the absolute addresses of the targets of the relative branches of a program loaded at
+/// address `base`.
+///
+/// Only branches with immediate relative targets (`jmp rel`, `call rel` and `jnz`) are resolved,
+/// as the targets of other branches are not known statically. The results are given in program
+/// order.
+pub fn resolve_targets(instructions: &[Instruction], base: usize) -> Vec<ResolvedTarget> {
+    let offsets = instruction_offsets(instructions);
+    let size = program_size(instructions);
+    instructions
+        .iter()
+        .zip(&offsets)
+        .enumerate()
+        .filter_map(|(index, (instruction, offset))| {
+            let Some(DerefOrImmediate::Immediate(jump_offset)) = instruction.body.relative_target()
+            else {
+                return None;
+            };
+            let target = jump_offset
+                .value
+                .to_isize()
+                .and_then(|jump_offset| offset.checked_add_signed(jump_offset))
+                .filter(|target| *target < size)
+                .ok_or(TargetError::OutOfProgram)
+                .and_then(|target| match offsets.binary_search(&target) {
+                    Ok(_) => Ok(base + target),
+                    Err(next) => Err(TargetError::MidInstruction(next - 1)),
+                });
+            Some(ResolvedTarget { index, target })
+        })
+        .collect()
+}
+
+/// A relative branch whose target is not the start of an instruction of the program.
+#[derive(Debug, Eq, PartialEq)]
+pub struct BranchError {
+    /// The index of the branch instruction in the program.
+    pub index: usize,
+    /// The reason the target is invalid.
+    pub error: TargetError,
+}
+impl Display for BranchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Instruction #{}: {}", self.index, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BranchError {}
+
+/// Checks that every relative branch of the program with an immediate target lands on the start
+/// of an instruction, as resolved by [resolve_targets].
+///
+/// The targets of absolute branches depend on the address the program is loaded at, and are
+/// therefore not checked.
+pub fn validate_branch_alignment(instructions: &[Instruction]) -> Result<(), Vec<BranchError>> {
+    let errors: Vec<BranchError> = resolve_targets(instructions, 0)
+        .into_iter()
+        .filter_map(|ResolvedTarget { index, target }| {
+            target.err().map(|error| BranchError { index, error })
+        })
+        .collect();
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// A relative branch that is most likely the result of a miscompilation.
+#[derive(Debug, Eq, PartialEq)]
+pub enum BranchWarning {
+    /// A branch with a zero offset, which branches to itself forever.
+    SelfLoop,
+    /// A branch whose offset is smaller than its own size, so that it lands on its own immediate.
+    IntoSelf,
+}
+impl Display for BranchWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BranchWarning::SelfLoop => write!(f, "Branch to itself"),
+            BranchWarning::IntoSelf => write!(f, "Branch into its own immediate"),
+        }
+    }
+}
+
+/// Returns the relative branches of the program that branch to themselves, along with the index
+/// of each one in the program.
+///
+/// Only branches with immediate relative targets (`jmp rel`, `call rel` and `jnz`) are checked.
+pub fn suspicious_branches(instructions: &[Instruction]) -> Vec<(usize, BranchWarning)> {
+    instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, instruction)| {
+            let Some(DerefOrImmediate::Immediate(jump_offset)) = instruction.body.relative_target()
+            else {
+                return None;
+            };
+            if jump_offset.value.is_zero() {
+                Some((index, BranchWarning::SelfLoop))
+            } else if jump_offset.value.is_positive()
+                && jump_offset.value < BigInt::from(instruction.body.op_size())
+            {
+                Some((index, BranchWarning::IntoSelf))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns a listing of the program, with the immediate targets of its relative branches replaced
+/// by labels placed before the instructions they land on.
+///
+/// A label is named after the word offset it marks, e.g. `LABEL_7`. Branches whose targets are
+/// not the start of an instruction or the end of the program are listed with their raw offsets.
+pub fn format_listing(instructions: &[Instruction]) -> String {
+    format_listing_with(instructions, ListingOptions::default())
+}
+
+/// Options for [format_listing_with].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ListingOptions {
+    /// Whether to mark the instructions taking two words with an appended `; size=2` comment.
+    pub size_comments: bool,
+}
+
+/// Same as [format_listing], with the given options.
+pub fn format_listing_with(instructions: &[Instruction], options: ListingOptions) -> String {
+    format_listing_impl(instructions, &[], options)
+}
+
+/// Same as [format_listing], for instructions some of which are labeled. The label of an
+/// instruction is placed on its own line, as `NAME:`, before it and after its offset label, if
+/// any.
+pub fn format_labeled_listing(instructions: &[LabeledInstruction]) -> String {
+    let labels: Vec<Option<&str>> =
+        instructions.iter().map(|instruction| instruction.label.as_deref()).collect();
+    let instructions: Vec<Instruction> =
+        instructions.iter().map(|instruction| instruction.instruction.clone()).collect();
+    format_listing_impl(&instructions, &labels, ListingOptions::default())
+}
+
+/// Formats the listing of [format_listing_with], with the given names placed before the
+/// instructions at the same indices. Instructions without names may be omitted at the end.
+fn format_listing_impl(
+    instructions: &[Instruction],
+    names: &[Option<&str>],
+    options: ListingOptions,
+) -> String {
+    let offsets = instruction_offsets(instructions);
+    let size = program_size(instructions);
+    let targets: Vec<Option<usize>> = instructions
+        .iter()
+        .zip(&offsets)
+        .map(|(instruction, offset)| {
+            let Some(DerefOrImmediate::Immediate(jump_offset)) = instruction.body.relative_target()
+            else {
+                return None;
+            };
+            let target = offset.checked_add_signed(jump_offset.value.to_isize()?)?;
+            (target == size || offsets.binary_search(&target).is_ok()).then_some(target)
+        })
+        .collect();
+    let mut labels: Vec<usize> = targets.iter().flatten().copied().collect();
+    labels.sort_unstable();
+    labels.dedup();
+
+    let mut listing = String::new();
+    for (index, ((instruction, offset), target)) in
+        instructions.iter().zip(&offsets).zip(&targets).enumerate()
+    {
+        if labels.binary_search(offset).is_ok() {
+            writeln!(listing, "LABEL_{offset}:").unwrap();
+        }
+        if let Some(Some(name)) = names.get(index) {
+            writeln!(listing, "{name}:").unwrap();
+        }
+        for hint in &instruction.hints {
+            writeln!(listing, "    %{{ {} %}}", hint.get_pythonic_hint().trim()).unwrap();
+        }
+        match (&instruction.body, target) {
+            (InstructionBody::Jump(_), Some(target)) => {
+                write!(listing, "    jmp rel LABEL_{target}")
+            }
+            (InstructionBody::Call(_), Some(target)) => {
+                write!(listing, "    call rel LABEL_{target}")
+            }
+            (InstructionBody::Jnz(insn), Some(target)) => {
+                write!(listing, "    jmp rel LABEL_{target} if {} != 0", insn.condition)
+            }
+            (body, _) => write!(listing, "    {body}"),
+        }
+        .unwrap();
+        if instruction.inc_ap {
+            listing.push_str(", ap++");
+        }
+        let op_size = instruction.body.op_size();
+        if options.size_comments && op_size > 1 {
+            write!(listing, "; size={op_size}").unwrap();
+        }
+        listing.push('\n');
+    }
+    if labels.last() == Some(&size) {
+        writeln!(listing, "LABEL_{size}:").unwrap();
+    }
+    listing
+}
+
+/// Returns the largest absolute offset of the cell references of the given instructions, or None
+/// if they have no cell references.
+///
+/// The inner offset of a double deref operand is not an offset of a cell reference, so it is not
+/// considered.
+pub fn max_offset(instructions: &[Instruction]) -> Option<u16> {
+    struct MaxOffsetVisitor(Option<u16>);
+    impl OperandVisitor for MaxOffsetVisitor {
+        fn visit_cell_ref(&mut self, cell: &CellRef) {
+            self.0 = self.0.max(Some(cell.offset.unsigned_abs()));
+        }
+    }
+
+    let mut visitor = MaxOffsetVisitor(None);
+    for instruction in instructions {
+        instruction.body.walk_operands(&mut visitor);
+    }
+    visitor.0
+}
+
+/// Splits the program into its functions, each ending with a `ret` instruction.
+///
+/// Instructions following the last `ret` of the program, if any, form the last segment.
+pub fn split_functions(instructions: &[Instruction]) -> Vec<&[Instruction]> {
+    instructions
+        .split_inclusive(|instruction| matches!(instruction.body, InstructionBody::Ret(_)))
+        .collect()
+}
+
+/// Returns the program made of the instructions of `a` followed by those of `b`.
+///
+/// The immediate targets of the absolute calls and jumps of `b` are offsets within `b`, so they
+/// are shifted by the size of `a`. Relative branches are left as is, as they keep their meaning
+/// when `b` is moved as a whole.
+pub fn concat_programs(mut a: Vec<Instruction>, b: Vec<Instruction>) -> Vec<Instruction> {
+    let shift = program_size(&a);
+    a.extend(b.into_iter().map(|mut instruction| {
+        let target = match &mut instruction.body {
+            InstructionBody::Call(insn) if !insn.relative => &mut insn.target,
+            InstructionBody::Jump(insn) if !insn.relative => &mut insn.target,
+            _ => return instruction,
+        };
+        if let DerefOrImmediate::Immediate(imm) = target {
+            imm.value += shift;
+        }
+        instruction
+    }));
+    a
+}
+
+/// Returns the indices of the calls of the program that are immediately followed by a `ret`.
+///
+/// The instruction following a call in the program is the one starting right after the call's
+/// words, where execution resumes once the callee returns.
+pub fn find_tail_calls(instructions: &[Instruction]) -> Vec<usize> {
+    instructions
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| {
+            matches!(
+                (&pair[0].body, &pair[1].body),
+                (InstructionBody::Call(_), InstructionBody::Ret(_))
+            )
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// A builtin component of the VM that instructions can invoke through an opcode extension.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Builtin {
+    Blake2s,
+    Keccak,
+}
+impl Display for Builtin {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Builtin::Blake2s => write!(f, "blake2s"),
+            Builtin::Keccak => write!(f, "keccak"),
+        }
+    }
+}
+
+/// Returns the builtins invoked by the instructions of the program.
+///
+/// Builtins accessed through memory segments, such as `range_check`, are not invoked by any
+/// instruction, and are therefore not reported.
+pub fn used_builtins(instructions: &[Instruction]) -> BTreeSet<Builtin> {
+    instructions
+        .iter()
+        .filter_map(|instruction| match instruction.body {
+            InstructionBody::Blake2sCompress(_) => Some(Builtin::Blake2s),
+            InstructionBody::Keccak(_) => Some(Builtin::Keccak),
+            InstructionBody::AddAp(_)
+            | InstructionBody::AssertEq(_)
+            | InstructionBody::QM31AssertEq(_)
+            | InstructionBody::Call(_)
+            | InstructionBody::Jump(_)
+            | InstructionBody::Jnz(_)
+            | InstructionBody::Ret(_) => None,
+        })
+        .collect()
+}
+
+/// Returns the jumps of the program that land on a chain of other jumps, each as the index of the
+/// jump along with the index of the instruction the chain ultimately leads to.
+///
+/// Only `jmp rel` instructions with immediate offsets are followed. A jump in the middle of a
+/// chain must have no hints and no `ap++`, so that skipping it has no effect. Chains that loop
+/// are not reported.
+pub fn find_jump_chains(instructions: &[Instruction]) -> Vec<(usize, usize)> {
+    let offsets = instruction_offsets(instructions);
+    // The index of the instruction the jump at the given index lands on.
+    let jump_target = |index: usize| {
+        let instruction = instructions.get(index)?;
+        let InstructionBody::Jump(insn) = &instruction.body else {
+            return None;
+        };
+        if !insn.relative {
+            return None;
+        }
+        let target = offsets[index].checked_add_signed(insn.target.as_immediate()?.to_isize()?)?;
+        offsets.binary_search(&target).ok()
+    };
+    let is_transparent =
+        |instruction: &Instruction| !instruction.inc_ap && instruction.hints.is_empty();
+    (0..instructions.len())
+        .filter_map(|index| {
+            let mut target = jump_target(index)?;
+            let mut hops = 0;
+            while is_transparent(&instructions[target]) {
+                let Some(next) = jump_target(target) else {
+                    break;
+                };
+                hops += 1;
+                if hops > instructions.len() {
+                    // The chain loops.
+                    return None;
+                }
+                target = next;
+            }
+            (hops > 0).then_some((index, target))
+        })
+        .collect()
+}
+
+/// Returns a hash of the instructions of the program, including their hints, to be used as the
+/// key of a cache of build artifacts.
+///
+/// The hash is computed over the text of each instruction: its body in assembly syntax, its ap++
+/// flag, and the pythonic text of its hints. It therefore does not depend on the platform or the
+/// run, and is defined for every instruction, including ones that cannot be encoded.
+pub fn program_fingerprint(instructions: &[Instruction]) -> u64 {
+    let mut hasher = Fnv1aHasher::default();
+    for instruction in instructions {
+        write!(hasher, "{}", instruction.body).unwrap();
+        // Separates the parts of an instruction, as a byte that is not part of any UTF-8 string.
+        hasher.write_byte(0xfe);
+        hasher.write_byte(u8::from(instruction.inc_ap));
+        for hint in &instruction.hints {
+            hasher.write_byte(0xfe);
+            hasher.write_str(&hint.get_pythonic_hint()).unwrap();
+        }
+        // Separates the instructions, as another such byte.
+        hasher.write_byte(0xff);
+    }
+    hasher.0
+}
+
+/// The 64-bit FNV-1a hash, which, unlike the hashers of the standard library, is stable.
+struct Fnv1aHasher(u64);
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+impl Fnv1aHasher {
+    fn write_byte(&mut self, byte: u8) {
+        self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+    }
+}
+impl Write for Fnv1aHasher {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the indices of the `assert_eq` instructions whose destination cell is written again by
+/// a later instruction before it is read.
+///
+/// Memory is write-once, so the later write is a re-assertion, checking that the cell holds the
+/// same value, and the earlier store is only used as the operand of that check. Note that the
+/// flagged store therefore cannot simply be removed: the later instruction would then write the
+/// cell instead, and the equality of the two stored values would no longer be asserted. Removing
+/// it is only sound along with the re-assertion, or if that equality is known to hold.
+///
+/// Each store is followed along the straight-line code after it, and is conservatively taken to
+/// be read if the code branches, changes ap by an unknown amount, reads memory through a pointer,
+/// or has a hint whose accesses are not known, before the cell is written again.
+pub fn dead_stores(instructions: &[Instruction]) -> Vec<usize> {
+    (0..instructions.len()).filter(|index| is_dead_store(&instructions[*index..])).collect()
+}
+
+/// Returns true if the first instruction of `instructions` is a dead store, as described by
+/// [dead_stores].
+fn is_dead_store(instructions: &[Instruction]) -> bool {
+    let (store, rest) = instructions.split_first().unwrap();
+    let InstructionBody::AssertEq(insn) = &store.body else {
+        return false;
+    };
+    // The cells, by register and offset from the value of the register at the store.
+    let slot = |cell: &CellRef, ap_change: i64| match cell.register {
+        Register::AP => (Register::AP, ap_change + i64::from(cell.offset)),
+        Register::FP => (Register::FP, i64::from(cell.offset)),
+    };
+    let stored = slot(&insn.a, 0);
+    let mut ap_change = i64::from(store.inc_ap);
+    for instruction in rest {
+        for hint in &instruction.hints {
+            let Some(accesses) = hint.accesses() else {
+                return false;
+            };
+            let mut cells = accesses.reads.iter().chain(&accesses.writes);
+            if cells.any(|cell| slot(cell, ap_change) == stored) {
+                return false;
+            }
+        }
+        let body = &instruction.body;
+        let reads_through_pointer = match body {
+            InstructionBody::AddAp(insn) => insn.operand.as_double_deref().is_some(),
+            InstructionBody::AssertEq(insn) => insn.b.as_double_deref().is_some(),
+            InstructionBody::Blake2sCompress(_) | InstructionBody::Keccak(_) => true,
+            _ => false,
+        };
+        if body.control_flow() != ControlFlow::Fallthrough
+            || reads_through_pointer
+            || body.reads().iter().any(|cell| slot(cell, ap_change) == stored)
+        {
+            return false;
+        }
+        if body.writes().iter().any(|cell| slot(cell, ap_change) == stored) {
+            return true;
+        }
+        ap_change += match body {
+            InstructionBody::AddAp(_) => match body.immediate().and_then(|imm| imm.to_i64()) {
+                Some(change) => change,
+                None => return false,
+            },
+            _ => 0,
+        } + i64::from(instruction.inc_ap);
+    }
+    false
+}
+
+/// A difference between the instructions of two programs at the same index.
+#[derive(Debug, Eq, PartialEq)]
+pub enum InstrDiff {
+    /// The instructions differ, in the parts marked as true.
+    Instruction { body: bool, inc_ap: bool, hints: bool },
+    /// Only one of the programs has an instruction at the index.
+    Missing,
+}
+
+/// Returns the index of the first instruction that differs between the two programs, along with
+/// how it differs, or None if the programs are equal.
+pub fn first_difference(a: &[Instruction], b: &[Instruction]) -> Option<(usize, InstrDiff)> {
+    if let Some((index, (a, b))) = a.iter().zip(b).enumerate().find(|(_, (a, b))| a != b) {
+        return Some((
+            index,
+            InstrDiff::Instruction {
+                body: a.body != b.body,
+                inc_ap: a.inc_ap != b.inc_ap,
+                hints: a.hints != b.hints,
+            },
+        ));
+    }
+    (a.len() != b.len()).then(|| (a.len().min(b.len()), InstrDiff::Missing))
+}
+
+/// Returns all the hints of the given instructions, in program order.
+pub fn all_hints(instructions: &[Instruction]) -> impl Iterator<Item = &Hint> {
+    instructions.iter().flat_map(|instruction| &instruction.hints)
+}
+
+/// Returns the number of occurrences of each distinct hint of the given instructions, keyed by
+/// the pythonic representation of the hint, in order of first occurrence.
+pub fn hint_histogram(instructions: &[Instruction]) -> OrderedHashMap<String, usize> {
+    let mut histogram = OrderedHashMap::<String, usize>::default();
+    for hint in all_hints(instructions) {
+        *histogram.entry(hint.get_pythonic_hint()).or_default() += 1;
+    }
+    histogram
+}
+
+/// Returns the sorted offsets of the targets of the branches of the program, or None if some
+/// branch target is not known statically, or is not the start of an instruction or the end of the
+/// program.
+///
+/// A program for which this returns Some may have instructions removed, as long as its branches
+/// are then patched by [relocate_branches].
+pub(crate) fn static_branch_targets(
+    instructions: &[Instruction],
+    offsets: &[usize],
+    size: usize,
+) -> Option<Vec<usize>> {
+    let mut targets = Vec::new();
+    for (instruction, offset) in instructions.iter().zip(offsets) {
+        let jump_offset = match instruction.body.relative_target() {
+            Some(DerefOrImmediate::Immediate(jump_offset)) => jump_offset,
+            Some(DerefOrImmediate::Deref(_)) => return None,
+            None if matches!(
+                instruction.body,
+                InstructionBody::Call(_) | InstructionBody::Jump(_)
+            ) =>
+            {
+                return None;
+            }
+            None => continue,
+        };
+        let target = offset.checked_add_signed(jump_offset.value.to_isize()?)?;
+        if target != size && offsets.binary_search(&target).is_err() {
+            return None;
+        }
+        targets.push(target);
+    }
+    targets.sort_unstable();
+    Some(targets)
+}
+
+/// Patches the offsets of the relative branches of a program some of whose instructions were
+/// removed, given the remaining instructions along with their offsets in the original program.
+///
+/// The original program must have had only static branch targets, as checked by
+/// [static_branch_targets]. A branch to a removed instruction is patched to land on the next
+/// remaining instruction. Returns None if some branch lands before the start of the program, as
+/// such a branch has no offset in it to relocate to.
+pub(crate) fn relocate_branches(kept: Vec<(usize, Instruction)>) -> Option<Vec<Instruction>> {
+    let old_offsets: Vec<usize> = kept.iter().map(|(offset, _)| *offset).collect();
+    let mut new_offsets = Vec::with_capacity(kept.len());
+    let mut new_size = 0;
+    for (_, instruction) in &kept {
+        new_offsets.push(new_size);
+        new_size += instruction.body.op_size();
+    }
+    let relocate = |old: usize| match old_offsets.binary_search(&old) {
+        Ok(index) | Err(index) => new_offsets.get(index).copied().unwrap_or(new_size),
+    };
+    kept.into_iter()
+        .zip(&new_offsets)
+        .map(|((old_offset, mut instruction), new_offset)| {
+            if let Some(DerefOrImmediate::Immediate(imm)) = instruction.body.relative_target_mut() {
+                let old_target = old_offset.checked_add_signed(imm.value.to_isize()?)?;
+                imm.value = BigInt::from(relocate(old_target)) - new_offset;
+            }
+            Some(instruction)
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+//! Conversion of programs to and from the JSON program format loaded by the Cairo VM.
+
+use core::fmt::Display;
+
+use num_bigint::BigInt;
+use serde_json::{Map, Value, json};
+
+use crate::assembler::AssembleError;
+use crate::decoder::{DecodeError, decode_instruction_mod};
+use crate::hints::{Hint, PythonicHint};
+use crate::instructions::Instruction;
+use crate::parser::ParseError;
+use crate::program::instruction_offsets;
+
+#[cfg(test)]
+#[path = "program_json_test.rs"]
+mod test;
+
+/// Returns the prime of the field of the Cairo VM, `2^251 + 17 * 2^192 + 1`.
+pub fn stark_prime() -> BigInt {
+    (BigInt::from(1) << 251) + (BigInt::from(17) << 192) + 1
+}
+
+/// Returns the program in the JSON program format of the Cairo VM.
+///
+/// The `data` array holds the encoded words of the instructions in hex, with negative immediates
+/// reduced modulo [stark_prime]. The `hints` map is keyed by the pc, i.e. the word offset, of the
+/// instruction each hint is attached to, and holds the pythonic texts of the hints. As the
+/// program has no identifiers, the hints have no accessible scopes or references.
+///
+/// Fails if an instruction cannot be assembled, see [Instruction::assemble].
+pub fn to_program_json(instructions: &[Instruction]) -> Result<Value, AssembleError> {
+    let prime = stark_prime();
+    let mut data = Vec::new();
+    let mut hints = Map::new();
+    for (instruction, pc) in instructions.iter().zip(instruction_offsets(instructions)) {
+        if !instruction.hints.is_empty() {
+            let pc_hints = instruction
+                .hints
+                .iter()
+                .map(|hint| {
+                    json!({
+                        "code": hint.get_pythonic_hint().trim(),
+                        "accessible_scopes": [],
+                        "flow_tracking_data": {
+                            "ap_tracking": { "group": 0, "offset": 0 },
+                            "reference_ids": {},
+                        },
+                    })
+                })
+                .collect();
+            hints.insert(pc.to_string(), Value::Array(pc_hints));
+        }
+        let words = instruction.encode_mod(&prime)?;
+        data.extend(words.iter().map(|word| Value::String(format!("{word:#x}"))));
+    }
+    Ok(json!({
+        "prime": format!("{prime:#x}"),
+        "data": data,
+        "hints": hints,
+        "builtins": [],
+        "main_scope": "__main__",
+        "identifiers": {},
+        "reference_manager": { "references": [] },
+        "attributes": [],
+        "debug_info": null,
+    }))
+}
+
+/// An error encountered while importing a program from its JSON program format.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ImportError {
+    /// The given field is missing, or is not of the expected JSON type.
+    InvalidField(&'static str),
+    /// The word at the given index of `data` is not a hex string.
+    InvalidWord(usize),
+    /// The instruction at the given pc could not be decoded.
+    Decode { pc: usize, error: DecodeError },
+    /// The hints at the given pc are not attached to the start of an instruction.
+    UnalignedHint(usize),
+    /// A hint at the given pc is not supported.
+    Hint { pc: usize, error: ParseError },
+}
+impl Display for ImportError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ImportError::InvalidField(field) => write!(f, "Invalid `{field}` field"),
+            ImportError::InvalidWord(index) => write!(f, "Invalid word #{index}"),
+            ImportError::Decode { pc, error } => write!(f, "Instruction at pc {pc}: {error}"),
+            ImportError::UnalignedHint(pc) => write!(f, "Hints at pc {pc} are mid instruction"),
+            ImportError::Hint { pc, error } => write!(f, "Hint at pc {pc}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Returns the instructions of a program in the JSON program format of the Cairo VM, as emitted by
+/// [to_program_json].
+///
+/// The words of `data` are decoded modulo the `prime` of the program, defaulting to
+/// [stark_prime], and the hints are attached to the instructions starting at their pcs. The hints
+/// are parsed from their pythonic texts, as described by the [core::str::FromStr] implementation
+/// of [Hint].
+pub fn from_program_json(value: &Value) -> Result<Vec<Instruction>, ImportError> {
+    let prime = match value.get("prime") {
+        Some(prime) => {
+            prime.as_str().and_then(parse_hex).ok_or(ImportError::InvalidField("prime"))?
+        }
+        None => stark_prime(),
+    };
+    let words = value
+        .get("data")
+        .and_then(Value::as_array)
+        .ok_or(ImportError::InvalidField("data"))?
+        .iter()
+        .enumerate()
+        .map(|(index, word)| {
+            word.as_str().and_then(parse_hex).ok_or(ImportError::InvalidWord(index))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while pc < words.len() {
+        let (instruction, size) = decode_instruction_mod(&words[pc..], &prime)
+            .map_err(|error| ImportError::Decode { pc, error })?;
+        instructions.push(instruction);
+        pc += size;
+    }
+
+    let Some(hints) = value.get("hints") else {
+        return Ok(instructions);
+    };
+    let hints = hints.as_object().ok_or(ImportError::InvalidField("hints"))?;
+    let offsets = instruction_offsets(&instructions);
+    for (pc, pc_hints) in hints {
+        let pc = pc.parse().map_err(|_| ImportError::InvalidField("hints"))?;
+        let index = offsets.binary_search(&pc).map_err(|_| ImportError::UnalignedHint(pc))?;
+        for hint in pc_hints.as_array().ok_or(ImportError::InvalidField("hints"))? {
+            let code = hint
+                .get("code")
+                .and_then(Value::as_str)
+                .ok_or(ImportError::InvalidField("code"))?;
+            let hint = code.parse::<Hint>().map_err(|error| ImportError::Hint { pc, error })?;
+            instructions[index].hints.push(hint);
+        }
+    }
+    Ok(instructions)
+}
+
+/// Parses a `0x` prefixed hex number.
+fn parse_hex(text: &str) -> Option<BigInt> {
+    BigInt::parse_bytes(text.strip_prefix("0x")?.as_bytes(), 16)
+}
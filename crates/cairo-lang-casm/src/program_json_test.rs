@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::PathBuf;
+
+use pretty_assertions::assert_eq;
+use serde_json::{Value, json};
+use test_case::test_case;
+use test_log::test;
+
+use super::{ImportError, from_program_json, stark_prime, to_program_json};
+use crate::assembler::AssembleError;
+use crate::casm;
+use crate::instructions::{Instruction, InstructionBody, KeccakInstruction, RetInstruction};
+use crate::operand::{CellRef, Register};
+
+#[test]
+fn test_to_program_json() {
+    let instructions = casm! {
+        [ap + 0] = (-1), ap++;
+        %{ memory[ap + 0] = segments.add() %}
+        %{ memory[ap + 1] = memory[fp + -3] < 45 %}
+        [ap + 0] = [fp + -3], ap++;
+        ret;
+    }
+    .instructions;
+    let program = to_program_json(&instructions).unwrap();
+    let minus_one = format!("{:#x}", stark_prime() - 1);
+    assert_eq!(
+        program["prime"],
+        "0x800000000000011000000000000000000000000000000000000000000000001"
+    );
+    assert_eq!(
+        program["data"],
+        json!(["0x480680017fff8000", minus_one, "0x480a7ffd7fff8000", "0x208b7fff7fff7ffe"])
+    );
+    let hint = |code: &str| {
+        json!({
+            "code": code,
+            "accessible_scopes": [],
+            "flow_tracking_data": {
+                "ap_tracking": { "group": 0, "offset": 0 },
+                "reference_ids": {},
+            },
+        })
+    };
+    // The hints are attached to the second instruction, at pc 2.
+    assert_eq!(
+        program["hints"],
+        json!({
+            "2": [
+                hint("memory[ap + 0] = segments.add()"),
+                hint("memory[ap + 1] = memory[fp + -3] < 45"),
+            ],
+        })
+    );
+    let keccak = Instruction::new(
+        InstructionBody::Keccak(KeccakInstruction {
+            state: CellRef { register: Register::FP, offset: -5 },
+            byte_count: CellRef { register: Register::FP, offset: -4 },
+            message: CellRef { register: Register::AP, offset: 3 },
+            finalize: false,
+        }),
+        true,
+    );
+    assert_eq!(to_program_json(&[keccak]), Err(AssembleError::NoOpcodeExtension));
+    let ret = Instruction::new(InstructionBody::Ret(RetInstruction {}), true);
+    assert_eq!(to_program_json(&[ret]), Err(AssembleError::InvalidIncAp));
+}
+
+#[test]
+fn test_from_program_json() {
+    let instructions = casm! {
+        %{ memory[ap + 0] = segments.add() %}
+        [ap + 0] = (-1), ap++;
+        %{ memory[ap + 0] = segments.add() %}
+        %{ memory[ap + 1] = memory[fp + -3] < 45 %}
+        [ap + 0] = [ap + -1] * [fp + -3], ap++;
+        jmp rel (-4) if [ap + -1] != 0;
+        ret;
+    }
+    .instructions;
+    let program = to_program_json(&instructions).unwrap();
+    assert_eq!(from_program_json(&program), Ok(instructions));
+
+    let mut unaligned = program.clone();
+    unaligned["hints"] = json!({ "1": [] });
+    assert_eq!(from_program_json(&unaligned), Err(ImportError::UnalignedHint(1)));
+    let mut invalid_word = program;
+    invalid_word["data"][1] = json!(5);
+    assert_eq!(from_program_json(&invalid_word), Err(ImportError::InvalidWord(1)));
+}
+
+/// Reads a compiled contract class from the test data of the starknet crate, and returns it along
+/// with its program in the JSON program format of the Cairo VM.
+fn read_compiled_contract_class(name: &str) -> (Value, Value) {
+    // Pop the "/cairo-lang-casm" suffix.
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_owned();
+    path.extend([
+        "cairo-lang-starknet",
+        "test_data",
+        &format!("{name}.compiled_contract_class.json"),
+    ]);
+    let contract: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let hints: serde_json::Map<String, Value> = contract["pythonic_hints"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|pc_hints| {
+            let codes = pc_hints[1].as_array().unwrap().iter();
+            (pc_hints[0].to_string(), codes.map(|code| json!({ "code": code })).collect())
+        })
+        .collect();
+    let program =
+        json!({ "prime": contract["prime"], "data": contract["bytecode"], "hints": hints });
+    (contract, program)
+}
+
+#[test_case("test_contract__test_contract"; "test_contract")]
+#[test_case("account__account"; "account")]
+fn test_from_compiled_contract_class(name: &str) {
+    let (contract, program) = read_compiled_contract_class(name);
+    let instructions = from_program_json(&program).unwrap();
+
+    // Exporting the program back gives the same words and hint texts, up to the surrounding
+    // whitespace trimmed by the export.
+    let exported = to_program_json(&instructions).unwrap();
+    assert_eq!(exported["data"], contract["bytecode"]);
+    let codes = |hints: &Value| -> Vec<(String, Vec<String>)> {
+        let hints = hints.as_object().unwrap().iter();
+        hints
+            .map(|(pc, pc_hints)| {
+                let pc_hints = pc_hints.as_array().unwrap().iter();
+                let codes = pc_hints.map(|hint| hint["code"].as_str().unwrap().trim().to_string());
+                (pc.clone(), codes.collect())
+            })
+            .collect()
+    };
+    assert_eq!(codes(&exported["hints"]), codes(&program["hints"]));
+
+    // The imported hints are the ones the contract was compiled with.
+    #[cfg(feature = "serde")]
+    {
+        let offsets = crate::program::instruction_offsets(&instructions);
+        for pc_hints in contract["hints"].as_array().unwrap() {
+            let index = offsets.binary_search(&(pc_hints[0].as_u64().unwrap() as usize)).unwrap();
+            let hints: Vec<crate::hints::Hint> =
+                serde_json::from_value(pc_hints[1].clone()).unwrap();
+            assert_eq!(instructions[index].hints, hints, "{}", instructions[index]);
+        }
+    }
+}
+
+/// Checks that all the hints of a contract using every libfunc parse from their pythonic texts.
+/// Its program holds constant data after its code, so it is not imported as a whole.
+#[cfg(feature = "serde")]
+#[test]
+fn test_parse_compiled_contract_class_hints() {
+    let (contract, _) = read_compiled_contract_class("libfuncs_coverage__libfuncs_coverage");
+    let pythonic_hints = contract["pythonic_hints"].as_array().unwrap();
+    let hints = contract["hints"].as_array().unwrap();
+    assert_eq!(pythonic_hints.len(), hints.len());
+    for (pythonic_pc_hints, pc_hints) in pythonic_hints.iter().zip(hints) {
+        let codes = pythonic_pc_hints[1].as_array().unwrap();
+        let expected: Vec<crate::hints::Hint> =
+            serde_json::from_value(pc_hints[1].clone()).unwrap();
+        assert_eq!(codes.len(), expected.len());
+        for (code, expected) in codes.iter().zip(expected) {
+            assert_eq!(code.as_str().unwrap().parse(), Ok(expected));
+        }
+    }
+}
@@ -0,0 +1,581 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use indoc::indoc;
+use test_log::test;
+
+use super::{
+    BranchError, BranchWarning, Builtin, InstrDiff, InstructionSink, ListingOptions, Program,
+    ProgramMetrics, ResolvedTarget, TargetError, all_hints, concat_programs, dead_stores,
+    find_jump_chains, find_tail_calls, first_difference, format_labeled_listing, format_listing,
+    format_listing_with, hint_histogram, instruction_offsets, max_offset, program_byte_size,
+    program_fingerprint, program_metrics, program_size, relocate_branches, resolve_targets,
+    split_functions, strip_hints, suspicious_branches, used_builtins, validate_branch_alignment,
+};
+use crate::ap_change::ApChange;
+use crate::casm;
+use crate::instructions::{
+    Blake2sCompressInstruction, Instruction, InstructionBody, KeccakInstruction, LabeledInstruction,
+};
+use crate::operand::{CellRef, Register, ap_cell_ref};
+
+#[test]
+fn test_program_display() {
+    let instructions: Vec<Instruction> = casm! {
+        %{ memory[ap + 0] = segments.add() %}
+        [ap + 0] = 5, ap++;
+        jmp rel 4;
+        ret;
+    }
+    .instructions;
+    let joined = instructions.iter().fold(String::new(), |mut joined, instruction| {
+        writeln!(joined, "{instruction}").unwrap();
+        joined
+    });
+    assert_eq!(Program(instructions).to_string(), joined);
+    assert_eq!(
+        joined,
+        indoc! {"
+            %{ memory[ap + 0] = segments.add() %}
+            [ap + 0] = 5, ap++
+            jmp rel 4
+            ret
+        "}
+    );
+    assert_eq!(Program::default().to_string(), "");
+}
+
+#[test]
+fn test_program_size_and_offsets() {
+    let instructions: Vec<Instruction> = casm! {
+        [ap + 0] = 5, ap++;
+        [ap + 0] = [ap + -1] + [fp + -3], ap++;
+        jmp rel 4;
+        [ap + 0] = [ap + -1] * 3, ap++;
+        ret;
+    }
+    .instructions;
+    assert_eq!(program_size(&instructions), 8);
+    assert_eq!(program_byte_size(&instructions), 256);
+    assert_eq!(program_byte_size(&[]), 0);
+    assert_eq!(instruction_offsets(&instructions), [0, 2, 3, 5, 7]);
+    assert_eq!(program_size(&[]), 0);
+    assert!(instruction_offsets(&[]).is_empty());
+}
+
+#[test]
+fn test_resolve_targets() {
+    let instructions: Vec<Instruction> = casm! {
+        // Offset 0.
+        [ap + 0] = 5, ap++;
+        // Offset 2: to offset 8.
+        jmp rel 6;
+        // Offset 4: to offset 0.
+        call rel (-4);
+        // Offset 6: to offset 2.
+        jmp rel (-4) if [ap + -1] != 0;
+        // Offset 8: not statically known.
+        jmp abs [fp + -3];
+        // Offset 9: to offset 9.
+        jmp rel 0;
+        // Offset 11: to offset 1, the middle of the first instruction.
+        jmp rel -10;
+        // Offset 13: to offset 17.
+        jmp rel 4;
+        // Offset 15: to offset -1, before the start of the program.
+        call rel (-16);
+        // Offset 17.
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        resolve_targets(&instructions, 100),
+        [
+            ResolvedTarget { index: 1, target: Ok(108) },
+            ResolvedTarget { index: 2, target: Ok(100) },
+            ResolvedTarget { index: 3, target: Ok(102) },
+            ResolvedTarget { index: 5, target: Ok(109) },
+            ResolvedTarget { index: 6, target: Err(TargetError::MidInstruction(0)) },
+            ResolvedTarget { index: 7, target: Ok(117) },
+            ResolvedTarget { index: 8, target: Err(TargetError::OutOfProgram) },
+        ]
+    );
+}
+
+#[test]
+fn test_relocate_branch_before_start() {
+    let instructions: Vec<Instruction> = casm! {
+        ap += 1;
+        // Offset 2: to offset -2, before the start of the program.
+        jmp rel (-4);
+        ret;
+    }
+    .instructions;
+    let kept = instruction_offsets(&instructions).into_iter().zip(instructions).collect();
+    assert_eq!(relocate_branches(kept), None);
+}
+
+#[test]
+fn test_suspicious_branches() {
+    let instructions: Vec<Instruction> = casm! {
+        jmp rel 0;
+        jmp rel 2;
+        jmp rel 1;
+        call rel 0;
+        jmp rel (-2) if [ap + -1] != 0;
+        jmp rel 0 if [ap + -1] != 0;
+        jmp abs 0;
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        suspicious_branches(&instructions),
+        [
+            (0, BranchWarning::SelfLoop),
+            (2, BranchWarning::IntoSelf),
+            (3, BranchWarning::SelfLoop),
+            (5, BranchWarning::SelfLoop),
+        ]
+    );
+}
+
+#[test]
+fn test_format_listing() {
+    let instructions: Vec<Instruction> = casm! {
+        // Offset 0.
+        [ap + 0] = 10, ap++;
+        // Offset 2.
+        %{ memory[ap + 0] = segments.add() %}
+        [ap + 0] = [ap + -1] + [fp + -3], ap++;
+        // Offset 3: to offset 2.
+        jmp rel (-1) if [ap + -1] != 0;
+        // Offset 5: to offset 9.
+        call rel 4;
+        // Offset 7: to offset 10, the end of the program.
+        jmp rel 3, ap++;
+        // Offset 9.
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        format_listing(&instructions),
+        indoc! {"
+                [ap + 0] = 10, ap++
+            LABEL_2:
+                %{ memory[ap + 0] = segments.add() %}
+                [ap + 0] = [ap + -1] + [fp + -3], ap++
+                jmp rel LABEL_2 if [ap + -1] != 0
+                call rel LABEL_9
+                jmp rel LABEL_10, ap++
+            LABEL_9:
+                ret
+            LABEL_10:
+        "}
+    );
+}
+
+#[test]
+fn test_max_offset() {
+    let instructions: Vec<Instruction> = casm! {
+        [ap + 0] = [fp + -3], ap++;
+        [ap + 5] = [[fp + 7] + 30000];
+        jmp rel 4 if [ap + -2] != 0;
+        ret;
+    }
+    .instructions;
+    assert_eq!(max_offset(&instructions), Some(7));
+
+    let instructions: Vec<Instruction> = casm! {
+        [ap + 0] = [fp + -32768] + [ap + 32767];
+        ap += 5;
+    }
+    .instructions;
+    assert_eq!(max_offset(&instructions), Some(32768));
+
+    let instructions: Vec<Instruction> = casm! {
+        ap += 5;
+        jmp rel 3;
+    }
+    .instructions;
+    assert_eq!(max_offset(&instructions), None);
+    assert_eq!(max_offset(&[]), None);
+}
+
+#[test]
+fn test_split_functions() {
+    let instructions: Vec<Instruction> = casm! {
+        [ap + 0] = 5, ap++;
+        call rel 3;
+        ret;
+        [ap + 0] = [fp + -3] + 1, ap++;
+        ret;
+    }
+    .instructions;
+    assert_eq!(split_functions(&instructions), [&instructions[..3], &instructions[3..]]);
+    assert_eq!(split_functions(&instructions[..4]), [&instructions[..3], &instructions[3..4]]);
+    assert!(split_functions(&[]).is_empty());
+}
+
+#[test]
+fn test_first_difference() {
+    let a: Vec<Instruction> = casm! {
+        [ap + 0] = 5, ap++;
+        [ap + 0] = [fp + -3], ap++;
+        [ap + 0] = [ap + -1] + [ap + -2];
+        ret;
+    }
+    .instructions;
+    let b: Vec<Instruction> = casm! {
+        [ap + 0] = 5, ap++;
+        [ap + 0] = [fp + -3], ap++;
+        [ap + 0] = [ap + -1] + [ap + -2], ap++;
+        ret;
+    }
+    .instructions;
+    assert_eq!(first_difference(&a, &a), None);
+    assert_eq!(
+        first_difference(&a, &b),
+        Some((2, InstrDiff::Instruction { body: false, inc_ap: true, hints: false }))
+    );
+    assert_eq!(first_difference(&a, &a[..3]), Some((3, InstrDiff::Missing)));
+    assert_eq!(first_difference(&[], &a), Some((0, InstrDiff::Missing)));
+}
+
+#[test]
+fn test_hint_histogram() {
+    let instructions: Vec<Instruction> = casm! {
+        %{ memory[ap + 0] = segments.add() %}
+        [ap + 0] = 5, ap++;
+        %{ memory[ap + 0] = memory[fp + -3] < 10 %}
+        %{ memory[ap + 1] = segments.add() %}
+        [ap + 0] = [ap + -1] + [fp + -3], ap++;
+        %{ memory[ap + 0] = segments.add() %}
+        ret;
+    }
+    .instructions;
+    assert_eq!(all_hints(&instructions).count(), 4);
+    assert_eq!(
+        hint_histogram(&instructions).into_iter().collect::<Vec<_>>(),
+        [
+            ("memory[ap + 0] = segments.add()".to_string(), 2),
+            ("memory[ap + 0] = memory[fp + -3] < 10".to_string(), 1),
+            ("memory[ap + 1] = segments.add()".to_string(), 1),
+        ]
+    );
+}
+
+#[test]
+fn test_strip_hints() {
+    let mut instructions: Vec<Instruction> = casm! {
+        %{ memory[ap + 0] = segments.add() %}
+        [ap + 0] = 5, ap++;
+        %{ memory[ap + 0] = memory[fp + -3] < 10 %}
+        jmp rel 2;
+        ret;
+    }
+    .instructions;
+    let without_hints: Vec<Instruction> =
+        instructions.iter().map(|instruction| instruction.without_hints()).collect();
+    let original = instructions.clone();
+    strip_hints(&mut instructions);
+    assert_eq!(instructions, without_hints);
+    for (stripped, original) in instructions.iter().zip(original) {
+        assert!(stripped.hints.is_empty());
+        assert_eq!(stripped, &Instruction { hints: vec![], ..original });
+    }
+}
+
+#[test]
+fn test_program_metrics() {
+    let instructions: Vec<Instruction> = casm! {
+        %{ memory[ap + 0] = segments.add() %}
+        %{ memory[ap + 1] = segments.add() %}
+        [ap + 0] = 5, ap++;
+        [ap + 0] = [ap + -1] + [fp + -3], ap++;
+        %{ memory[ap + 0] = memory[fp + -3] < 10 %}
+        jmp rel 4;
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        program_metrics(&instructions),
+        ProgramMetrics { word_count: 6, instruction_count: 4, hint_count: 3 }
+    );
+    assert_eq!(program_metrics(&[]), ProgramMetrics::default());
+}
+
+#[test]
+fn test_instruction_sink() {
+    let mut sink = InstructionSink::default();
+    assert_eq!(sink.ap_change(), ApChange::Known(0));
+    for instruction in casm! {
+        [ap + 0] = 5, ap++;
+        [ap + 0] = [ap + -1] * 3, ap++;
+        ap += 3;
+    }
+    .instructions
+    {
+        sink.push(instruction);
+    }
+    assert_eq!(sink.ap_change(), ApChange::Known(5));
+    assert_eq!(sink.word_offset(), 6);
+    // The values written when the ap change was 0 and 1.
+    assert_eq!(sink.ap_cell(0, 0), Some(ap_cell_ref(-5)));
+    assert_eq!(sink.ap_cell(1, 0), Some(ap_cell_ref(-4)));
+    assert_eq!(sink.ap_cell(6, 0), None);
+
+    for instruction in casm! { call rel 2; }.instructions {
+        sink.push(instruction);
+    }
+    assert_eq!(sink.ap_change(), ApChange::Unknown);
+    assert_eq!(sink.word_offset(), 8);
+    assert_eq!(sink.ap_cell(0, 0), None);
+    assert_eq!(sink.into_instructions().len(), 4);
+}
+
+#[test]
+fn test_concat_programs() {
+    let a = casm! {
+        [ap + 0] = 1, ap++;
+        ret;
+    }
+    .instructions;
+    let b = casm! {
+        call abs 4;
+        jmp rel 2;
+        jmp abs [fp + -3];
+        ret;
+    }
+    .instructions;
+    let expected = casm! {
+        [ap + 0] = 1, ap++;
+        ret;
+        call abs 7;
+        jmp rel 2;
+        jmp abs [fp + -3];
+        ret;
+    }
+    .instructions;
+    assert_eq!(concat_programs(a, b), expected);
+}
+
+#[test]
+fn test_validate_branch_alignment() {
+    let aligned = casm! {
+        jmp rel 4 if [fp + -3] != 0;
+        [ap + 0] = 1, ap++;
+        call rel (-4);
+        ret;
+    }
+    .instructions;
+    assert_eq!(validate_branch_alignment(&aligned), Ok(()));
+
+    let misaligned = casm! {
+        jmp rel 3 if [fp + -3] != 0;
+        [ap + 0] = 1, ap++;
+        jmp rel 1;
+        jmp rel 9;
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        validate_branch_alignment(&misaligned),
+        Err(vec![
+            BranchError { index: 0, error: TargetError::MidInstruction(1) },
+            BranchError { index: 2, error: TargetError::MidInstruction(2) },
+            BranchError { index: 3, error: TargetError::OutOfProgram },
+        ])
+    );
+}
+
+#[test]
+fn test_find_tail_calls() {
+    let instructions = casm! {
+        call rel 6;
+        [ap + 0] = [ap + -1], ap++;
+        call abs [fp + -3];
+        ret;
+        ret;
+    }
+    .instructions;
+    assert_eq!(find_tail_calls(&instructions), [2]);
+}
+
+#[test]
+fn test_used_builtins() {
+    let fp = |offset| CellRef { register: Register::FP, offset };
+    let mut instructions = casm! {
+        [ap + 0] = 1, ap++;
+        ret;
+    }
+    .instructions;
+    assert!(used_builtins(&instructions).is_empty());
+
+    let blake = Blake2sCompressInstruction {
+        state: fp(-5),
+        byte_count: fp(-4),
+        message: fp(-3),
+        finalize: false,
+    };
+    instructions.insert(1, Instruction::new(blake.clone().into(), true));
+    instructions.insert(1, Instruction::new(blake.into(), true));
+    assert_eq!(used_builtins(&instructions).into_iter().collect::<Vec<_>>(), [Builtin::Blake2s]);
+}
+
+#[test]
+fn test_format_listing_size_comments() {
+    let instructions: Vec<Instruction> = casm! {
+        [ap + 0] = 10, ap++;
+        [ap + 0] = [ap + -1] + [fp + -3], ap++;
+        jmp rel (-1) if [ap + -1] != 0;
+        ret;
+    }
+    .instructions;
+    assert_eq!(
+        format_listing_with(&instructions, ListingOptions { size_comments: true }),
+        indoc! {"
+                [ap + 0] = 10, ap++; size=2
+            LABEL_2:
+                [ap + 0] = [ap + -1] + [fp + -3], ap++
+                jmp rel LABEL_2 if [ap + -1] != 0; size=2
+                ret
+        "}
+    );
+    assert_eq!(
+        format_listing_with(&instructions, ListingOptions::default()),
+        format_listing(&instructions)
+    );
+}
+
+#[test]
+fn test_find_jump_chains() {
+    let instructions = casm! {
+        // Offset 0: to offset 4, then to offset 6, then to offset 8.
+        jmp rel 4;
+        // Offset 2: to offset 8.
+        jmp rel 6;
+        // Offset 4: to offset 6.
+        jmp rel 2;
+        // Offset 6: to offset 8.
+        jmp rel 2;
+        // Offset 8.
+        [ap + 0] = 1, ap++;
+        // Offset 10: to offset 12, which has an `ap++`.
+        jmp rel 2;
+        // Offset 12: to offset 14.
+        jmp rel 2, ap++;
+        // Offset 14: to itself.
+        jmp rel 0;
+        ret;
+    }
+    .instructions;
+    assert_eq!(find_jump_chains(&instructions), [(0, 4), (2, 4)]);
+}
+
+#[test]
+fn test_program_fingerprint() {
+    let program = || {
+        casm! {
+            %{ memory[ap + 0] = segments.add() %}
+            [ap + 0] = [fp + -3], ap++;
+            jmp rel 2;
+            ret;
+        }
+        .instructions
+    };
+    let fingerprint = program_fingerprint(&program());
+    assert_eq!(program_fingerprint(&program()), fingerprint);
+    // The fingerprint is stable, so that it can key a persistent cache.
+    assert_eq!(fingerprint, 0x9d79c9fba9fb8d52);
+
+    let mut changed_offset = program();
+    changed_offset[0] = casm!([ap + 0] = [fp + -4], ap++;).instructions.remove(0);
+    changed_offset[0].hints = program()[0].hints.clone();
+    assert_ne!(program_fingerprint(&changed_offset), fingerprint);
+
+    let mut changed_inc_ap = program();
+    changed_inc_ap[0].inc_ap = false;
+    assert_ne!(program_fingerprint(&changed_inc_ap), fingerprint);
+
+    let mut without_hints = program();
+    strip_hints(&mut without_hints);
+    assert_ne!(program_fingerprint(&without_hints), fingerprint);
+    // The empty program hashes to the offset basis of FNV-1a.
+    assert_eq!(program_fingerprint(&[]), 0xcbf29ce484222325);
+
+    // Instructions that cannot be encoded have a fingerprint as well.
+    let keccak = Instruction::new(
+        InstructionBody::Keccak(KeccakInstruction {
+            state: ap_cell_ref(-3),
+            byte_count: ap_cell_ref(-2),
+            message: ap_cell_ref(-1),
+            finalize: false,
+        }),
+        true,
+    );
+    assert_ne!(program_fingerprint(&[keccak]), program_fingerprint(&[]));
+}
+
+#[test]
+fn test_format_labeled_listing() {
+    let mut instructions = casm! {
+        call rel 3;
+        ret;
+        [ap + 0] = [fp + -3], ap++;
+        ret;
+    }
+    .instructions
+    .into_iter()
+    .map(LabeledInstruction::from)
+    .collect::<Vec<_>>();
+    instructions[2] = instructions[2].instruction.clone().with_label("double");
+    instructions[0].label = Some("main".into());
+    assert_eq!(
+        format_labeled_listing(&instructions),
+        indoc! {"
+            main:
+                call rel LABEL_3
+                ret
+            LABEL_3:
+            double:
+                [ap + 0] = [fp + -3], ap++
+                ret
+        "}
+    );
+    assert_eq!(instructions[2].to_string(), "double:\n[ap + 0] = [fp + -3], ap++");
+}
+
+#[test]
+fn test_dead_store_reasserted_before_read() {
+    let instructions = casm! {
+        // Reasserted, as `[ap + -2]`, before being read.
+        [ap + 0] = [fp + -3], ap++;
+        ap += 1;
+        [ap + -2] = [fp + -4];
+        ret;
+    }
+    .instructions;
+    assert_eq!(dead_stores(&instructions), [0]);
+}
+
+#[test]
+fn test_store_read_or_branched_before_reassertion() {
+    let instructions = casm! {
+        // Read by the following instruction.
+        [fp + 0] = 5;
+        [ap + 0] = [fp + 0] + 1, ap++;
+        [fp + 0] = 5;
+        // Followed by a branch.
+        [fp + 1] = 6;
+        jmp rel 2;
+        [fp + 1] = 7;
+        ret;
+    }
+    .instructions;
+    assert!(dead_stores(&instructions).is_empty());
+}
@@ -0,0 +1,358 @@
+//! Property testing support, generating arbitrary instructions and operands.
+
+use cairo_lang_utils::bigint::BigIntAsHex;
+use proptest::prelude::*;
+
+use crate::hints::{CoreHint, ExternalHint, Hint, StarknetHint};
+use crate::instructions::{
+    AddApInstruction, AssertEqInstruction, Blake2sCompressInstruction, CallInstruction,
+    InstructionBody, JnzInstruction, JumpInstruction, KeccakInstruction, RetInstruction,
+};
+use crate::operand::{BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand};
+
+/// Returns a strategy for immediate values, both positive and negative.
+fn immediate() -> impl Strategy<Value = BigIntAsHex> {
+    any::<i64>().prop_map(BigIntAsHex::from)
+}
+
+impl Arbitrary for Register {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![Just(Register::AP), Just(Register::FP)].boxed()
+    }
+}
+
+impl Arbitrary for CellRef {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        (any::<Register>(), any::<i16>())
+            .prop_map(|(register, offset)| CellRef { register, offset })
+            .boxed()
+    }
+}
+
+impl Arbitrary for DerefOrImmediate {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<CellRef>().prop_map(DerefOrImmediate::Deref),
+            immediate().prop_map(DerefOrImmediate::Immediate),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Operation {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![Just(Operation::Add), Just(Operation::Mul)].boxed()
+    }
+}
+
+impl Arbitrary for BinOpOperand {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        (any::<Operation>(), any::<CellRef>(), any::<DerefOrImmediate>())
+            .prop_map(|(op, a, b)| BinOpOperand { op, a, b })
+            .boxed()
+    }
+}
+
+impl Arbitrary for ResOperand {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<CellRef>().prop_map(ResOperand::Deref),
+            (any::<CellRef>(), any::<i16>())
+                .prop_map(|(cell, offset)| ResOperand::DoubleDeref(cell, offset)),
+            immediate().prop_map(ResOperand::Immediate),
+            any::<BinOpOperand>().prop_map(ResOperand::BinOp),
+        ]
+        .boxed()
+    }
+}
+
+/// Returns a strategy for res operands used as addresses by hints, which may not be immediates.
+fn address() -> impl Strategy<Value = ResOperand> {
+    prop_oneof![
+        any::<CellRef>().prop_map(ResOperand::Deref),
+        (any::<CellRef>(), any::<i16>())
+            .prop_map(|(cell, offset)| ResOperand::DoubleDeref(cell, offset)),
+        any::<BinOpOperand>().prop_map(ResOperand::BinOp),
+    ]
+}
+
+/// Generates the hints that have a pythonic text holding all of their operands, i.e. all hints
+/// but the deprecated ones and the cheatcode hint.
+///
+/// A `TestLessThanOrEqualAddress` is only generated with a binary operation `lhs`, as in its uses
+/// by the compiler, since its text is otherwise the same as that of a `TestLessThanOrEqual`.
+impl Arbitrary for Hint {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        let cell = any::<CellRef>;
+        let integer = any::<ResOperand>;
+        prop_oneof![
+            cell().prop_map(|dst| CoreHint::AllocSegment { dst }.into()),
+            (integer(), integer(), cell()).prop_map(|(lhs, rhs, dst)| CoreHint::TestLessThan {
+                lhs,
+                rhs,
+                dst
+            }
+            .into()),
+            (integer(), integer(), cell()).prop_map(|(lhs, rhs, dst)| {
+                CoreHint::TestLessThanOrEqual { lhs, rhs, dst }.into()
+            }),
+            (any::<BinOpOperand>(), address(), cell()).prop_map(|(lhs, rhs, dst)| {
+                let lhs = ResOperand::BinOp(lhs);
+                CoreHint::TestLessThanOrEqualAddress { lhs, rhs, dst }.into()
+            }),
+            (integer(), integer(), cell(), cell()).prop_map(|(lhs, rhs, high, low)| {
+                CoreHint::WideMul128 { lhs, rhs, high, low }.into()
+            }),
+            (integer(), integer(), cell(), cell()).prop_map(|(lhs, rhs, quotient, remainder)| {
+                CoreHint::DivMod { lhs, rhs, quotient, remainder }.into()
+            }),
+            ((integer(), integer(), integer(), integer()), (cell(), cell(), cell(), cell()),)
+                .prop_map(
+                    |(
+                        (dividend0, dividend1, divisor0, divisor1),
+                        (quotient0, quotient1, remainder0, remainder1),
+                    )| {
+                        CoreHint::Uint256DivMod {
+                            dividend0,
+                            dividend1,
+                            divisor0,
+                            divisor1,
+                            quotient0,
+                            quotient1,
+                            remainder0,
+                            remainder1,
+                        }
+                        .into()
+                    }
+                ),
+            (
+                (integer(), integer(), integer(), integer(), integer(), integer()),
+                (cell(), cell(), cell(), cell(), cell(), cell()),
+            )
+                .prop_map(
+                    |(
+                        (dividend0, dividend1, dividend2, dividend3, divisor0, divisor1),
+                        (quotient0, quotient1, quotient2, quotient3, remainder0, remainder1),
+                    )| {
+                        CoreHint::Uint512DivModByUint256 {
+                            dividend0,
+                            dividend1,
+                            dividend2,
+                            dividend3,
+                            divisor0,
+                            divisor1,
+                            quotient0,
+                            quotient1,
+                            quotient2,
+                            quotient3,
+                            remainder0,
+                            remainder1,
+                        }
+                        .into()
+                    }
+                ),
+            (integer(), cell()).prop_map(|(value, dst)| CoreHint::SquareRoot { value, dst }.into()),
+            ((integer(), integer()), (cell(), cell(), cell(), cell(), cell())).prop_map(
+                |(
+                    (value_low, value_high),
+                    (
+                        sqrt0,
+                        sqrt1,
+                        remainder_low,
+                        remainder_high,
+                        sqrt_mul_2_minus_remainder_ge_u128,
+                    ),
+                )| {
+                    CoreHint::Uint256SquareRoot {
+                        value_low,
+                        value_high,
+                        sqrt0,
+                        sqrt1,
+                        remainder_low,
+                        remainder_high,
+                        sqrt_mul_2_minus_remainder_ge_u128,
+                    }
+                    .into()
+                }
+            ),
+            (integer(), integer(), integer(), cell(), cell()).prop_map(
+                |(value, scalar, max_x, x, y)| {
+                    CoreHint::LinearSplit { value, scalar, max_x, x, y }.into()
+                }
+            ),
+            address().prop_map(|segment_arena_ptr| {
+                CoreHint::AllocFelt252Dict { segment_arena_ptr }.into()
+            }),
+            (address(), integer()).prop_map(|(dict_ptr, key)| {
+                CoreHint::Felt252DictEntryInit { dict_ptr, key }.into()
+            }),
+            (address(), integer()).prop_map(|(dict_ptr, value)| {
+                CoreHint::Felt252DictEntryUpdate { dict_ptr, value }.into()
+            }),
+            (address(), cell()).prop_map(|(dict_end_ptr, dict_index)| {
+                CoreHint::GetSegmentArenaIndex { dict_end_ptr, dict_index }.into()
+            }),
+            (address(), integer(), integer(), cell(), cell()).prop_map(
+                |(dict_accesses, ptr_diff, n_accesses, big_keys, first_key)| {
+                    CoreHint::InitSquashData {
+                        dict_accesses,
+                        ptr_diff,
+                        n_accesses,
+                        big_keys,
+                        first_key,
+                    }
+                    .into()
+                }
+            ),
+            address().prop_map(|range_check_ptr| {
+                CoreHint::GetCurrentAccessIndex { range_check_ptr }.into()
+            }),
+            cell().prop_map(|should_skip_loop| {
+                CoreHint::ShouldSkipSquashLoop { should_skip_loop }.into()
+            }),
+            cell().prop_map(|index_delta_minus1| {
+                CoreHint::GetCurrentAccessDelta { index_delta_minus1 }.into()
+            }),
+            cell().prop_map(|should_continue| {
+                CoreHint::ShouldContinueSquashLoop { should_continue }.into()
+            }),
+            cell().prop_map(|next_key| CoreHint::GetNextDictKey { next_key }.into()),
+            (address(), integer(), integer()).prop_map(|(range_check_ptr, a, b)| {
+                CoreHint::AssertLeFindSmallArcs { range_check_ptr, a, b }.into()
+            }),
+            cell().prop_map(|skip_exclude_a_flag| {
+                CoreHint::AssertLeIsFirstArcExcluded { skip_exclude_a_flag }.into()
+            }),
+            cell().prop_map(|skip_exclude_b_minus_a| {
+                CoreHint::AssertLeIsSecondArcExcluded { skip_exclude_b_minus_a }.into()
+            }),
+            (cell(), cell()).prop_map(|(x, y)| CoreHint::RandomEcPoint { x, y }.into()),
+            (integer(), cell()).prop_map(|(val, sqrt)| CoreHint::FieldSqrt { val, sqrt }.into()),
+            (address(), address())
+                .prop_map(|(start, end)| CoreHint::DebugPrint { start, end }.into()),
+            (integer(), cell())
+                .prop_map(|(size, dst)| CoreHint::AllocConstantSize { size, dst }.into()),
+            (
+                (integer(), integer(), integer(), integer()),
+                (cell(), cell(), cell(), cell(), cell(), cell()),
+            )
+                .prop_map(
+                    |(
+                        (b0, b1, n0, n1),
+                        (g0_or_no_inv, g1_option, s_or_r0, s_or_r1, t_or_k0, t_or_k1),
+                    )| {
+                        CoreHint::U256InvModN {
+                            b0,
+                            b1,
+                            n0,
+                            n1,
+                            g0_or_no_inv,
+                            g1_option,
+                            s_or_r0,
+                            s_or_r1,
+                            t_or_k0,
+                            t_or_k1,
+                        }
+                        .into()
+                    }
+                ),
+            (integer(), address(), integer(), address()).prop_map(
+                |(n_add_mods, add_mod_builtin, n_mul_mods, mul_mod_builtin)| {
+                    CoreHint::EvalCircuit {
+                        n_add_mods,
+                        add_mod_builtin,
+                        n_mul_mods,
+                        mul_mod_builtin,
+                    }
+                    .into()
+                }
+            ),
+            address().prop_map(|system| StarknetHint::SystemCall { system }.into()),
+            (address(), address()).prop_map(|(src, dst)| ExternalHint::AddRelocationRule {
+                src,
+                dst
+            }
+            .into()),
+            (integer(), cell())
+                .prop_map(|(index, dst)| ExternalHint::WriteRunParam { index, dst }.into()),
+            (address(), address())
+                .prop_map(|(start, end)| ExternalHint::AddMarker { start, end }.into()),
+            integer().prop_map(|flag| ExternalHint::AddTrace { flag }.into()),
+        ]
+        .boxed()
+    }
+}
+
+/// Generates instruction bodies of all kinds. Note that the keccak instruction cannot be assembled,
+/// as it has no opcode extension yet.
+impl Arbitrary for InstructionBody {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<ResOperand>()
+                .prop_map(|operand| InstructionBody::AddAp(AddApInstruction { operand })),
+            (any::<CellRef>(), any::<ResOperand>())
+                .prop_map(|(a, b)| InstructionBody::AssertEq(AssertEqInstruction { a, b })),
+            // The QM31 opcode extension only supports binary operations.
+            (any::<CellRef>(), any::<BinOpOperand>()).prop_map(|(a, b)| {
+                InstructionBody::QM31AssertEq(
+                    AssertEqInstruction { a, b: ResOperand::BinOp(b) }.into(),
+                )
+            }),
+            (any::<DerefOrImmediate>(), any::<bool>()).prop_map(|(target, relative)| {
+                InstructionBody::Call(CallInstruction { target, relative })
+            }),
+            (any::<DerefOrImmediate>(), any::<bool>()).prop_map(|(target, relative)| {
+                InstructionBody::Jump(JumpInstruction { target, relative })
+            }),
+            (any::<DerefOrImmediate>(), any::<CellRef>()).prop_map(|(jump_offset, condition)| {
+                InstructionBody::Jnz(JnzInstruction { jump_offset, condition })
+            }),
+            Just(InstructionBody::Ret(RetInstruction {})),
+            (any::<CellRef>(), any::<CellRef>(), any::<CellRef>(), any::<bool>()).prop_map(
+                |(state, byte_count, message, finalize)| {
+                    InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+                        state,
+                        byte_count,
+                        message,
+                        finalize,
+                    })
+                }
+            ),
+            (any::<CellRef>(), any::<CellRef>(), any::<CellRef>(), any::<bool>()).prop_map(
+                |(state, byte_count, message, finalize)| {
+                    InstructionBody::Keccak(KeccakInstruction {
+                        state,
+                        byte_count,
+                        message,
+                        finalize,
+                    })
+                }
+            ),
+        ]
+        .boxed()
+    }
+}
@@ -0,0 +1,397 @@
+use crate::hints::{CoreHint, CoreHintBase, DeprecatedHint, ExternalHint, Hint, StarknetHint};
+use crate::instructions::{InstructionBody, QM31AssertEqInstruction};
+use crate::operand::{CellRef, DerefOrImmediate, ResOperand};
+
+#[cfg(test)]
+#[path = "visitor_test.rs"]
+mod test;
+
+/// A visitor over the operands of an instruction.
+///
+/// Composite operands are visited before the operands nested within them, so for example the
+/// cell references within a [ResOperand] are visited (by [Self::visit_cell_ref]) right after the
+/// [ResOperand] itself.
+pub trait OperandVisitor {
+    fn visit_cell_ref(&mut self, _cell: &CellRef) {}
+    fn visit_deref_or_immediate(&mut self, _operand: &DerefOrImmediate) {}
+    fn visit_res_operand(&mut self, _operand: &ResOperand) {}
+}
+
+/// A visitor over the operands of an instruction, allowing rewriting them in place.
+///
+/// Visits in the same order as [OperandVisitor]. The nested operands of a composite operand are
+/// visited after it was visited, so they reflect any rewrite done to it.
+pub trait OperandVisitorMut {
+    fn visit_cell_ref(&mut self, _cell: &mut CellRef) {}
+    fn visit_deref_or_immediate(&mut self, _operand: &mut DerefOrImmediate) {}
+    fn visit_res_operand(&mut self, _operand: &mut ResOperand) {}
+}
+
+impl InstructionBody {
+    /// Visits all the operands of the instruction, in the order of the fields of its variant.
+    pub fn walk_operands(&self, visitor: &mut impl OperandVisitor) {
+        match self {
+            InstructionBody::AddAp(insn) => walk_res_operand(&insn.operand, visitor),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => {
+                visitor.visit_cell_ref(&insn.a);
+                walk_res_operand(&insn.b, visitor);
+            }
+            InstructionBody::Call(insn) => walk_deref_or_immediate(&insn.target, visitor),
+            InstructionBody::Jump(insn) => walk_deref_or_immediate(&insn.target, visitor),
+            InstructionBody::Jnz(insn) => {
+                walk_deref_or_immediate(&insn.jump_offset, visitor);
+                visitor.visit_cell_ref(&insn.condition);
+            }
+            InstructionBody::Ret(_) => {}
+            InstructionBody::Blake2sCompress(insn) => {
+                for cell in &insn.cell_refs() {
+                    visitor.visit_cell_ref(cell);
+                }
+            }
+            InstructionBody::Keccak(insn) => {
+                visitor.visit_cell_ref(&insn.state);
+                visitor.visit_cell_ref(&insn.byte_count);
+                visitor.visit_cell_ref(&insn.message);
+            }
+        }
+    }
+
+    /// Visits all the operands of the instruction mutably, in the same order as
+    /// [Self::walk_operands].
+    pub fn walk_operands_mut(&mut self, visitor: &mut impl OperandVisitorMut) {
+        match self {
+            InstructionBody::AddAp(insn) => walk_res_operand_mut(&mut insn.operand, visitor),
+            InstructionBody::AssertEq(insn)
+            | InstructionBody::QM31AssertEq(QM31AssertEqInstruction(insn)) => {
+                visitor.visit_cell_ref(&mut insn.a);
+                walk_res_operand_mut(&mut insn.b, visitor);
+            }
+            InstructionBody::Call(insn) => walk_deref_or_immediate_mut(&mut insn.target, visitor),
+            InstructionBody::Jump(insn) => walk_deref_or_immediate_mut(&mut insn.target, visitor),
+            InstructionBody::Jnz(insn) => {
+                walk_deref_or_immediate_mut(&mut insn.jump_offset, visitor);
+                visitor.visit_cell_ref(&mut insn.condition);
+            }
+            InstructionBody::Ret(_) => {}
+            InstructionBody::Blake2sCompress(insn) => {
+                for cell in insn.cell_refs_mut() {
+                    visitor.visit_cell_ref(cell);
+                }
+            }
+            InstructionBody::Keccak(insn) => {
+                visitor.visit_cell_ref(&mut insn.state);
+                visitor.visit_cell_ref(&mut insn.byte_count);
+                visitor.visit_cell_ref(&mut insn.message);
+            }
+        }
+    }
+}
+
+impl Hint {
+    /// Visits all the operands of the hint mutably, in the order of the fields of its variant.
+    ///
+    /// Allows transforms of the cells referenced by instructions to keep their hints in sync.
+    pub fn walk_operands_mut(&mut self, visitor: &mut impl OperandVisitorMut) {
+        match self {
+            Hint::Core(CoreHintBase::Core(hint)) => walk_core_hint_mut(hint, visitor),
+            Hint::Core(CoreHintBase::Deprecated(hint)) => walk_deprecated_hint_mut(hint, visitor),
+            Hint::Starknet(hint) => walk_starknet_hint_mut(hint, visitor),
+            Hint::External(hint) => walk_external_hint_mut(hint, visitor),
+        }
+    }
+}
+
+fn walk_deref_or_immediate(operand: &DerefOrImmediate, visitor: &mut impl OperandVisitor) {
+    visitor.visit_deref_or_immediate(operand);
+    if let DerefOrImmediate::Deref(cell) = operand {
+        visitor.visit_cell_ref(cell);
+    }
+}
+
+fn walk_res_operand(operand: &ResOperand, visitor: &mut impl OperandVisitor) {
+    visitor.visit_res_operand(operand);
+    match operand {
+        ResOperand::Deref(cell) | ResOperand::DoubleDeref(cell, _) => visitor.visit_cell_ref(cell),
+        ResOperand::Immediate(_) => {}
+        ResOperand::BinOp(bin_op) => {
+            visitor.visit_cell_ref(&bin_op.a);
+            walk_deref_or_immediate(&bin_op.b, visitor);
+        }
+    }
+}
+
+fn walk_deref_or_immediate_mut(
+    operand: &mut DerefOrImmediate,
+    visitor: &mut impl OperandVisitorMut,
+) {
+    visitor.visit_deref_or_immediate(operand);
+    if let DerefOrImmediate::Deref(cell) = operand {
+        visitor.visit_cell_ref(cell);
+    }
+}
+
+fn walk_res_operand_mut(operand: &mut ResOperand, visitor: &mut impl OperandVisitorMut) {
+    visitor.visit_res_operand(operand);
+    match operand {
+        ResOperand::Deref(cell) | ResOperand::DoubleDeref(cell, _) => visitor.visit_cell_ref(cell),
+        ResOperand::Immediate(_) => {}
+        ResOperand::BinOp(bin_op) => {
+            visitor.visit_cell_ref(&mut bin_op.a);
+            walk_deref_or_immediate_mut(&mut bin_op.b, visitor);
+        }
+    }
+}
+
+fn walk_core_hint_mut(hint: &mut CoreHint, visitor: &mut impl OperandVisitorMut) {
+    match hint {
+        CoreHint::AllocSegment { dst } => visitor.visit_cell_ref(dst),
+        CoreHint::TestLessThan { lhs, rhs, dst } => {
+            walk_res_operand_mut(lhs, visitor);
+            walk_res_operand_mut(rhs, visitor);
+            visitor.visit_cell_ref(dst);
+        }
+        CoreHint::TestLessThanOrEqual { lhs, rhs, dst } => {
+            walk_res_operand_mut(lhs, visitor);
+            walk_res_operand_mut(rhs, visitor);
+            visitor.visit_cell_ref(dst);
+        }
+        CoreHint::TestLessThanOrEqualAddress { lhs, rhs, dst } => {
+            walk_res_operand_mut(lhs, visitor);
+            walk_res_operand_mut(rhs, visitor);
+            visitor.visit_cell_ref(dst);
+        }
+        CoreHint::WideMul128 { lhs, rhs, high, low } => {
+            walk_res_operand_mut(lhs, visitor);
+            walk_res_operand_mut(rhs, visitor);
+            visitor.visit_cell_ref(high);
+            visitor.visit_cell_ref(low);
+        }
+        CoreHint::DivMod { lhs, rhs, quotient, remainder } => {
+            walk_res_operand_mut(lhs, visitor);
+            walk_res_operand_mut(rhs, visitor);
+            visitor.visit_cell_ref(quotient);
+            visitor.visit_cell_ref(remainder);
+        }
+        CoreHint::Uint256DivMod {
+            dividend0,
+            dividend1,
+            divisor0,
+            divisor1,
+            quotient0,
+            quotient1,
+            remainder0,
+            remainder1,
+        } => {
+            walk_res_operand_mut(dividend0, visitor);
+            walk_res_operand_mut(dividend1, visitor);
+            walk_res_operand_mut(divisor0, visitor);
+            walk_res_operand_mut(divisor1, visitor);
+            visitor.visit_cell_ref(quotient0);
+            visitor.visit_cell_ref(quotient1);
+            visitor.visit_cell_ref(remainder0);
+            visitor.visit_cell_ref(remainder1);
+        }
+        CoreHint::Uint512DivModByUint256 {
+            dividend0,
+            dividend1,
+            dividend2,
+            dividend3,
+            divisor0,
+            divisor1,
+            quotient0,
+            quotient1,
+            quotient2,
+            quotient3,
+            remainder0,
+            remainder1,
+        } => {
+            walk_res_operand_mut(dividend0, visitor);
+            walk_res_operand_mut(dividend1, visitor);
+            walk_res_operand_mut(dividend2, visitor);
+            walk_res_operand_mut(dividend3, visitor);
+            walk_res_operand_mut(divisor0, visitor);
+            walk_res_operand_mut(divisor1, visitor);
+            visitor.visit_cell_ref(quotient0);
+            visitor.visit_cell_ref(quotient1);
+            visitor.visit_cell_ref(quotient2);
+            visitor.visit_cell_ref(quotient3);
+            visitor.visit_cell_ref(remainder0);
+            visitor.visit_cell_ref(remainder1);
+        }
+        CoreHint::SquareRoot { value, dst } => {
+            walk_res_operand_mut(value, visitor);
+            visitor.visit_cell_ref(dst);
+        }
+        CoreHint::Uint256SquareRoot {
+            value_low,
+            value_high,
+            sqrt0,
+            sqrt1,
+            remainder_low,
+            remainder_high,
+            sqrt_mul_2_minus_remainder_ge_u128,
+        } => {
+            walk_res_operand_mut(value_low, visitor);
+            walk_res_operand_mut(value_high, visitor);
+            visitor.visit_cell_ref(sqrt0);
+            visitor.visit_cell_ref(sqrt1);
+            visitor.visit_cell_ref(remainder_low);
+            visitor.visit_cell_ref(remainder_high);
+            visitor.visit_cell_ref(sqrt_mul_2_minus_remainder_ge_u128);
+        }
+        CoreHint::LinearSplit { value, scalar, max_x, x, y } => {
+            walk_res_operand_mut(value, visitor);
+            walk_res_operand_mut(scalar, visitor);
+            walk_res_operand_mut(max_x, visitor);
+            visitor.visit_cell_ref(x);
+            visitor.visit_cell_ref(y);
+        }
+        CoreHint::AllocFelt252Dict { segment_arena_ptr } => {
+            walk_res_operand_mut(segment_arena_ptr, visitor)
+        }
+        CoreHint::Felt252DictEntryInit { dict_ptr, key } => {
+            walk_res_operand_mut(dict_ptr, visitor);
+            walk_res_operand_mut(key, visitor);
+        }
+        CoreHint::Felt252DictEntryUpdate { dict_ptr, value } => {
+            walk_res_operand_mut(dict_ptr, visitor);
+            walk_res_operand_mut(value, visitor);
+        }
+        CoreHint::GetSegmentArenaIndex { dict_end_ptr, dict_index } => {
+            walk_res_operand_mut(dict_end_ptr, visitor);
+            visitor.visit_cell_ref(dict_index);
+        }
+        CoreHint::InitSquashData { dict_accesses, ptr_diff, n_accesses, big_keys, first_key } => {
+            walk_res_operand_mut(dict_accesses, visitor);
+            walk_res_operand_mut(ptr_diff, visitor);
+            walk_res_operand_mut(n_accesses, visitor);
+            visitor.visit_cell_ref(big_keys);
+            visitor.visit_cell_ref(first_key);
+        }
+        CoreHint::GetCurrentAccessIndex { range_check_ptr } => {
+            walk_res_operand_mut(range_check_ptr, visitor)
+        }
+        CoreHint::ShouldSkipSquashLoop { should_skip_loop } => {
+            visitor.visit_cell_ref(should_skip_loop)
+        }
+        CoreHint::GetCurrentAccessDelta { index_delta_minus1 } => {
+            visitor.visit_cell_ref(index_delta_minus1)
+        }
+        CoreHint::ShouldContinueSquashLoop { should_continue } => {
+            visitor.visit_cell_ref(should_continue)
+        }
+        CoreHint::GetNextDictKey { next_key } => visitor.visit_cell_ref(next_key),
+        CoreHint::AssertLeFindSmallArcs { range_check_ptr, a, b } => {
+            walk_res_operand_mut(range_check_ptr, visitor);
+            walk_res_operand_mut(a, visitor);
+            walk_res_operand_mut(b, visitor);
+        }
+        CoreHint::AssertLeIsFirstArcExcluded { skip_exclude_a_flag } => {
+            visitor.visit_cell_ref(skip_exclude_a_flag)
+        }
+        CoreHint::AssertLeIsSecondArcExcluded { skip_exclude_b_minus_a } => {
+            visitor.visit_cell_ref(skip_exclude_b_minus_a)
+        }
+        CoreHint::RandomEcPoint { x, y } => {
+            visitor.visit_cell_ref(x);
+            visitor.visit_cell_ref(y);
+        }
+        CoreHint::FieldSqrt { val, sqrt } => {
+            walk_res_operand_mut(val, visitor);
+            visitor.visit_cell_ref(sqrt);
+        }
+        CoreHint::DebugPrint { start, end } => {
+            walk_res_operand_mut(start, visitor);
+            walk_res_operand_mut(end, visitor);
+        }
+        CoreHint::AllocConstantSize { size, dst } => {
+            walk_res_operand_mut(size, visitor);
+            visitor.visit_cell_ref(dst);
+        }
+        CoreHint::U256InvModN {
+            b0,
+            b1,
+            n0,
+            n1,
+            g0_or_no_inv,
+            g1_option,
+            s_or_r0,
+            s_or_r1,
+            t_or_k0,
+            t_or_k1,
+        } => {
+            walk_res_operand_mut(b0, visitor);
+            walk_res_operand_mut(b1, visitor);
+            walk_res_operand_mut(n0, visitor);
+            walk_res_operand_mut(n1, visitor);
+            visitor.visit_cell_ref(g0_or_no_inv);
+            visitor.visit_cell_ref(g1_option);
+            visitor.visit_cell_ref(s_or_r0);
+            visitor.visit_cell_ref(s_or_r1);
+            visitor.visit_cell_ref(t_or_k0);
+            visitor.visit_cell_ref(t_or_k1);
+        }
+        CoreHint::EvalCircuit { n_add_mods, add_mod_builtin, n_mul_mods, mul_mod_builtin } => {
+            walk_res_operand_mut(n_add_mods, visitor);
+            walk_res_operand_mut(add_mod_builtin, visitor);
+            walk_res_operand_mut(n_mul_mods, visitor);
+            walk_res_operand_mut(mul_mod_builtin, visitor);
+        }
+    }
+}
+
+fn walk_deprecated_hint_mut(hint: &mut DeprecatedHint, visitor: &mut impl OperandVisitorMut) {
+    match hint {
+        DeprecatedHint::AssertCurrentAccessIndicesIsEmpty => {}
+        DeprecatedHint::AssertAllAccessesUsed { n_used_accesses } => {
+            visitor.visit_cell_ref(n_used_accesses)
+        }
+        DeprecatedHint::AssertAllKeysUsed => {}
+        DeprecatedHint::AssertLeAssertThirdArcExcluded => {}
+        DeprecatedHint::AssertLtAssertValidInput { a, b } => {
+            walk_res_operand_mut(a, visitor);
+            walk_res_operand_mut(b, visitor);
+        }
+        DeprecatedHint::Felt252DictRead { dict_ptr, key, value_dst } => {
+            walk_res_operand_mut(dict_ptr, visitor);
+            walk_res_operand_mut(key, visitor);
+            visitor.visit_cell_ref(value_dst);
+        }
+        DeprecatedHint::Felt252DictWrite { dict_ptr, key, value } => {
+            walk_res_operand_mut(dict_ptr, visitor);
+            walk_res_operand_mut(key, visitor);
+            walk_res_operand_mut(value, visitor);
+        }
+    }
+}
+
+fn walk_starknet_hint_mut(hint: &mut StarknetHint, visitor: &mut impl OperandVisitorMut) {
+    match hint {
+        StarknetHint::SystemCall { system } => walk_res_operand_mut(system, visitor),
+        StarknetHint::Cheatcode { input_start, input_end, output_start, output_end, .. } => {
+            walk_res_operand_mut(input_start, visitor);
+            walk_res_operand_mut(input_end, visitor);
+            visitor.visit_cell_ref(output_start);
+            visitor.visit_cell_ref(output_end);
+        }
+    }
+}
+
+fn walk_external_hint_mut(hint: &mut ExternalHint, visitor: &mut impl OperandVisitorMut) {
+    match hint {
+        ExternalHint::AddRelocationRule { src, dst } => {
+            walk_res_operand_mut(src, visitor);
+            walk_res_operand_mut(dst, visitor);
+        }
+        ExternalHint::WriteRunParam { index, dst } => {
+            walk_res_operand_mut(index, visitor);
+            visitor.visit_cell_ref(dst);
+        }
+        ExternalHint::AddMarker { start, end } => {
+            walk_res_operand_mut(start, visitor);
+            walk_res_operand_mut(end, visitor);
+        }
+        ExternalHint::AddTrace { flag } => walk_res_operand_mut(flag, visitor),
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::ToString, vec, vec::Vec};
+
+use pretty_assertions::assert_eq;
+use test_log::test;
+
+use super::{OperandVisitor, OperandVisitorMut};
+use crate::instructions::{Blake2sCompressInstruction, InstructionBody};
+use crate::operand::{CellRef, DerefOrImmediate, Register, ResOperand};
+use crate::{casm, deref};
+
+/// Collects all the visited cell references.
+#[derive(Default)]
+struct CellCollector {
+    cells: Vec<CellRef>,
+    res_operands: usize,
+    deref_or_immediates: usize,
+}
+impl OperandVisitor for CellCollector {
+    fn visit_cell_ref(&mut self, cell: &CellRef) {
+        self.cells.push(*cell);
+    }
+    fn visit_deref_or_immediate(&mut self, _operand: &DerefOrImmediate) {
+        self.deref_or_immediates += 1;
+    }
+    fn visit_res_operand(&mut self, _operand: &ResOperand) {
+        self.res_operands += 1;
+    }
+}
+
+fn collect(body: &InstructionBody) -> CellCollector {
+    let mut collector = CellCollector::default();
+    body.walk_operands(&mut collector);
+    collector
+}
+
+#[test]
+fn test_collect_cell_refs() {
+    let ctx = casm! {
+        [fp + -3] = [ap + 1] * [fp + 2];
+        [ap + 0] = [[fp + 1] + 3];
+        jmp rel [fp + 4] if [ap + 5] != 0;
+        call rel 3;
+        ret;
+    };
+    let bodies: Vec<_> = ctx.instructions.into_iter().map(|instruction| instruction.body).collect();
+
+    let collector = collect(&bodies[0]);
+    assert_eq!(collector.cells, vec![deref!([fp - 3]), deref!([ap + 1]), deref!([fp + 2])]);
+    assert_eq!((collector.res_operands, collector.deref_or_immediates), (1, 1));
+
+    let collector = collect(&bodies[1]);
+    assert_eq!(collector.cells, vec![deref!([ap]), deref!([fp + 1])]);
+    assert_eq!((collector.res_operands, collector.deref_or_immediates), (1, 0));
+
+    let collector = collect(&bodies[2]);
+    assert_eq!(collector.cells, vec![deref!([fp + 4]), deref!([ap + 5])]);
+    assert_eq!((collector.res_operands, collector.deref_or_immediates), (0, 1));
+
+    let collector = collect(&bodies[3]);
+    assert_eq!(collector.cells, vec![]);
+    assert_eq!((collector.res_operands, collector.deref_or_immediates), (0, 1));
+
+    assert_eq!(collect(&bodies[4]).cells, vec![]);
+
+    let blake2s = InstructionBody::Blake2sCompress(Blake2sCompressInstruction {
+        state: deref!([fp - 5]),
+        byte_count: deref!([fp - 4]),
+        message: deref!([ap - 1]),
+        finalize: false,
+    });
+    assert_eq!(collect(&blake2s).cells, vec![deref!([fp - 5]), deref!([fp - 4]), deref!([ap - 1])]);
+}
+
+/// Moves all fp based cell references to be ap based.
+struct FpToAp;
+impl OperandVisitorMut for FpToAp {
+    fn visit_cell_ref(&mut self, cell: &mut CellRef) {
+        cell.register = Register::AP;
+    }
+}
+
+#[test]
+fn test_rewrite_cell_refs() {
+    let mut ctx = casm! {
+        [fp + -3] = [ap + 1] + [fp + 2];
+        jmp rel 5 if [fp + 5] != 0;
+    };
+    for instruction in &mut ctx.instructions {
+        instruction.body.walk_operands_mut(&mut FpToAp);
+    }
+    assert_eq!(ctx.instructions[0].to_string(), "[ap + -3] = [ap + 1] + [ap + 2]");
+    assert_eq!(ctx.instructions[1].to_string(), "jmp rel 5 if [ap + 5] != 0");
+}
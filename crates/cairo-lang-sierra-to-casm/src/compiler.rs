@@ -141,6 +141,9 @@ impl Display for CairoProgram {
     }
 }
 
+/// The panic message for assembling an instruction the compiler does not generate.
+const ASSEMBLE_ERROR_MSG: &str = "Compiled programs only hold instructions with an encoding.";
+
 impl CairoProgram {
     /// Creates an assembled representation of the program.
     pub fn assemble(&self) -> AssembledCairoProgram {
@@ -160,11 +163,11 @@ impl CairoProgram {
             if !instruction.hints.is_empty() {
                 hints.push((bytecode.len(), instruction.hints.clone()))
             }
-            bytecode.extend(instruction.assemble().encode().into_iter())
+            bytecode.extend(instruction.encode().expect(ASSEMBLE_ERROR_MSG))
         }
         let [ref ret_bytecode] = Instruction::new(InstructionBody::Ret(RetInstruction {}), false)
-            .assemble()
-            .encode()[..]
+            .encode()
+            .expect(ASSEMBLE_ERROR_MSG)[..]
         else {
             panic!("`ret` instruction should be a single word.")
         };
@@ -178,7 +181,7 @@ impl CairoProgram {
                 "All footer instructions must have no hints since these cannot be added to the \
                  hints dict."
             );
-            bytecode.extend(instruction.assemble().encode().into_iter())
+            bytecode.extend(instruction.encode().expect(ASSEMBLE_ERROR_MSG))
         }
         AssembledCairoProgram { bytecode, hints }
     }